@@ -0,0 +1,253 @@
+use directory_sync::model::bisync::ConflictResolution;
+use directory_sync::model::cli::{CmdArgs, DaemonArgs, LogArgs, WatchArgs};
+use directory_sync::model::config::Config;
+use directory_sync::model::event::EventKind;
+use directory_sync::model::history::{HistoryEntry, HistoryResult};
+use directory_sync::model::options::SyncOptions;
+use directory_sync::model::report::SyncReport;
+use directory_sync::model::schedule::Direction;
+use directory_sync::service::core;
+use directory_sync::service::daemon;
+use directory_sync::service::folder;
+use directory_sync::service::history;
+use directory_sync::service::link;
+use directory_sync::service::output;
+use directory_sync::service::prompt::Prompt;
+use directory_sync::{Listing, Syncer};
+use std::io;
+use std::time::Duration;
+
+// Asks on stdin, prompts go to stderr when stdout carries json events
+pub struct StdinPrompt;
+
+impl Prompt for StdinPrompt {
+    fn confirm(&self) -> bool {
+        output::text("Enter y to continue!");
+        return read_answer() == "y";
+    }
+
+    fn resolve_conflict(&self, path: &str) -> Option<ConflictResolution> {
+        output::text(&format!(
+            "Conflict: {} changed on both sides. Keep [l]ocal, [r]emote, [b]oth or [s]kip?",
+            path
+        ));
+        return match read_answer().as_str() {
+            "l" => Some(ConflictResolution::Local),
+            "r" => Some(ConflictResolution::Remote),
+            "b" => Some(ConflictResolution::Both),
+            _ => None,
+        };
+    }
+}
+
+fn read_answer() -> String {
+    let mut user_input = String::from("");
+    io::stdin()
+        .read_line(&mut user_input)
+        .expect("Failed to read line");
+    return user_input.trim().to_string();
+}
+
+pub fn ls(cmd_args: CmdArgs, is_link: bool, config: &Config) -> Vec<Result<(), String>> {
+    let target = cmd_args.target;
+    let relative_path = cmd_args.relative_path;
+    let ssh_servers = &config.ssh_servers;
+    let mut results = Vec::new();
+    if is_link {
+        let Some(link) = config.links.get(&target) else {
+            return vec![Err(format!("Error locating link: {}", target))];
+        };
+        let paths: Vec<Option<String>> = match link.paths.is_empty() {
+            true => vec![relative_path],
+            false => link.paths.iter().cloned().map(Some).collect(),
+        };
+        for path in paths.iter() {
+            results.push(core::ls(&link.local, ssh_servers, path).map(print_listing));
+            results.push(core::ls(&link.target, ssh_servers, path).map(print_listing));
+        }
+    } else {
+        let Some(folder) = config.folders.get(&target) else {
+            return vec![Err(format!("Error locating folder: {}", target))];
+        };
+        results.push(core::ls(folder, ssh_servers, &relative_path).map(print_listing));
+    }
+    return results;
+}
+
+fn print_listing(listing: Listing) -> () {
+    if output::is_json() {
+        output::emit(EventKind::Listing {
+            path: listing.path,
+            lines: listing.lines,
+        });
+    } else {
+        println!("{}", listing.lines.join("\n"));
+    }
+}
+
+pub fn pull(
+    cmd_args: CmdArgs,
+    is_link: bool,
+    cli_options: SyncOptions,
+    config: &Config,
+) -> Vec<Result<SyncReport, String>> {
+    let syncer = Syncer::new(config).options(cli_options);
+    if is_link {
+        return syncer.run_link(&cmd_args.target, Direction::Pull, &cmd_args.relative_path);
+    }
+    let current_folder = folder::get_current_dir().expect("Unable to resolve cwd");
+    let Some(folder) = config.folders.get(&cmd_args.target) else {
+        return vec![Err(format!("Error locating folder: {}", cmd_args.target))];
+    };
+    return syncer.run_folders(folder, &current_folder, &cmd_args.relative_path);
+}
+
+pub fn push(
+    cmd_args: CmdArgs,
+    is_link: bool,
+    cli_options: SyncOptions,
+    config: &Config,
+) -> Vec<Result<SyncReport, String>> {
+    let syncer = Syncer::new(config).options(cli_options);
+    if is_link {
+        return syncer.run_link(&cmd_args.target, Direction::Push, &cmd_args.relative_path);
+    }
+    let current_folder = folder::get_current_dir().expect("Unable to resolve cwd");
+    let Some(folder) = config.folders.get(&cmd_args.target) else {
+        return vec![Err(format!("Error locating folder: {}", cmd_args.target))];
+    };
+    return syncer.run_folders(&current_folder, folder, &cmd_args.relative_path);
+}
+
+pub fn verify(cmd_args: CmdArgs, is_link: bool, config: &Config) -> Vec<Result<(), String>> {
+    let target = cmd_args.target;
+    let relative_path = cmd_args.relative_path;
+    let ssh_servers = &config.ssh_servers;
+    let mut results = Vec::new();
+    if is_link {
+        let Some(link) = config.links.get(&target) else {
+            return vec![Err(format!("Error locating link: {}", target))];
+        };
+        let paths: Vec<Option<String>> = match link.paths.is_empty() || relative_path.is_some() {
+            true => vec![relative_path],
+            false => link.paths.iter().cloned().map(Some).collect(),
+        };
+        for path in paths.iter() {
+            results.push(core::verify(
+                &link.local,
+                &link.target,
+                ssh_servers,
+                path,
+                &link.checksum,
+                &link.exclude,
+            ));
+        }
+    } else {
+        let current_folder = folder::get_current_dir().expect("Unable to resolve cwd");
+        let Some(folder) = config.folders.get(&target) else {
+            return vec![Err(format!("Error locating folder: {}", target))];
+        };
+        results.push(core::verify(
+            &current_folder,
+            folder,
+            ssh_servers,
+            &relative_path,
+            &None,
+            &[],
+        ));
+    }
+    return results;
+}
+
+pub fn sync(
+    cmd_args: CmdArgs,
+    cli_options: SyncOptions,
+    config: &Config,
+) -> Vec<Result<SyncReport, String>> {
+    return Syncer::new(config).options(cli_options).run_link(
+        &cmd_args.target,
+        Direction::Sync,
+        &cmd_args.relative_path,
+    );
+}
+
+pub fn log(log_args: LogArgs) -> Vec<Result<(), String>> {
+    let entries = match history::read() {
+        Ok(entries) => entries,
+        Err(err) => return vec![Err(err)],
+    };
+    let mut entries: Vec<HistoryEntry> = entries
+        .into_iter()
+        .filter(|entry| match &log_args.target {
+            Some(target) => history::matches(entry, target),
+            None => true,
+        })
+        .collect();
+    if let Some(limit) = log_args.limit {
+        entries.drain(..entries.len().saturating_sub(limit));
+    }
+    for entry in entries {
+        if output::is_json() {
+            output::emit(EventKind::History(entry));
+            continue;
+        }
+        let result = match entry.result {
+            HistoryResult::Ok => "ok",
+            HistoryResult::Failed => "failed",
+            HistoryResult::Skipped => "skipped",
+        };
+        println!(
+            "{} {:<4} {:<7} {} -> {} ({}, {} files, {} bytes, {}ms){}",
+            entry.time,
+            entry.direction,
+            result,
+            entry.from,
+            entry.to,
+            entry.mode,
+            entry.files,
+            entry.bytes,
+            entry.duration_ms,
+            entry
+                .error
+                .map(|err| format!(": {}", err))
+                .unwrap_or_default()
+        );
+    }
+    return vec![Ok(())];
+}
+
+pub fn watch(
+    watch_args: WatchArgs,
+    cli_options: SyncOptions,
+    config: &Config,
+) -> Vec<Result<(), String>> {
+    let target = watch_args.target;
+    let relative_path = watch_args.relative_path;
+    let Some(link) = config.links.get(&target) else {
+        return vec![Err(format!("Error locating link: {}", target))];
+    };
+    if link.partial_only && link.paths.is_empty() && !Option::is_some(&relative_path) {
+        log::warn!("{} is partial only, ending task", link.name);
+        return Vec::new();
+    }
+    let options = link::options(link, &cli_options);
+    return vec![directory_sync::service::watch::watch(
+        link,
+        &config.work_folder,
+        &config.ssh_servers,
+        &relative_path,
+        &options,
+        Duration::from_millis(watch_args.debounce),
+    )];
+}
+
+pub fn daemon(
+    daemon_args: DaemonArgs,
+    cli_options: SyncOptions,
+    config: &Config,
+) -> Vec<Result<(), String>> {
+    if daemon_args.status {
+        return vec![daemon::status()];
+    }
+    return vec![daemon::run(config, cli_options)];
+}
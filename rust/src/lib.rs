@@ -0,0 +1,19 @@
+#![allow(
+    clippy::needless_return,
+    clippy::unused_unit,
+    clippy::vec_init_then_push
+)]
+
+pub mod model;
+pub mod service;
+
+pub use model::config::Config;
+pub use model::folder::Folder;
+pub use model::link::Link;
+pub use model::listing::Listing;
+pub use model::options::SyncOptions;
+pub use model::plan::PlannedSync;
+pub use model::report::SyncReport;
+pub use model::schedule::Direction;
+pub use model::ssh::SshServer;
+pub use service::syncer::Syncer;
@@ -1,13 +1,33 @@
-mod model;
-mod service;
+#![allow(
+    clippy::needless_return,
+    clippy::unused_unit,
+    clippy::vec_init_then_push
+)]
+
+mod cli;
 use clap::Parser;
-use model::cli::CliCmd;
-use service::config::parse_config;
-use service::config::read_config;
+use directory_sync::model::bisync::ConflictResolution;
+use directory_sync::model::cli::CliCmd;
+use directory_sync::model::event::EventKind;
+use directory_sync::model::options::SyncOptions;
+use directory_sync::model::report::SyncReport;
+use directory_sync::service::config::parse_config;
+use directory_sync::service::config::read_config;
+use directory_sync::service::logger;
+use directory_sync::service::output;
+use directory_sync::service::prompt;
+use std::process::exit;
+use std::sync::Arc;
+use std::time::Instant;
 
-use crate::service::cli::ls;
-use crate::service::cli::pull;
-use crate::service::cli::push;
+use crate::cli::daemon;
+use crate::cli::log;
+use crate::cli::ls;
+use crate::cli::pull;
+use crate::cli::push;
+use crate::cli::sync;
+use crate::cli::verify;
+use crate::cli::watch;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -18,34 +38,168 @@ pub struct Args {
     pub link: bool,
     #[arg(short, long, action)]
     pub force: bool,
+    /// Compare the destination against a manifest of the source after syncing
+    #[arg(long, action)]
+    pub verify: bool,
+    /// Start over instead of resuming archives left by an interrupted sync
+    #[arg(long, action)]
+    pub fresh: bool,
+    /// Limit the transfer to this many Kbit/s, overriding bandwidth_limit
+    #[arg(long)]
+    pub bwlimit: Option<u32>,
+    /// Run tar and extract steps with nice and ionice
+    #[arg(long, action)]
+    pub low_priority: bool,
+    /// Sync this many paths of a link at the same time
+    #[arg(short, long)]
+    pub jobs: Option<usize>,
+    /// Pack every path of a link into one archive, staged transfers only
+    #[arg(long, action)]
+    pub bundle: bool,
+    /// Settle files changed on both sides during sync without asking
+    #[arg(long, value_enum)]
+    pub conflict: Option<ConflictResolution>,
+    /// Wait for a destination locked by another sync, the default for watch and daemon
+    #[arg(long, action, conflicts_with = "no_wait")]
+    pub wait: bool,
+    /// Fail right away when the destination is locked, the default otherwise
+    #[arg(long, action)]
+    pub no_wait: bool,
+    /// Emit newline delimited JSON events on stdout, see docs/EVENTS.md
+    #[arg(long, action)]
+    pub json: bool,
+    /// Only print warnings and errors
+    #[arg(short, long, action, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Print subprocess output (-v) and internal details (-vv)
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+    /// Append every log record as JSON to this file, regardless of verbosity
+    #[arg(long)]
+    pub log_file: Option<String>,
 }
 
 fn main() {
-    let config = read_config().expect("Error reading config");
-    let (ssh_servers, folders, links, work_folder) = parse_config(config);
     let args = Args::parse();
+    // No logger is there to report this through
+    if let Err(err) = logger::init(
+        logger::level_from_args(args.quiet, args.verbose),
+        &args.log_file,
+    ) {
+        eprintln!("{}", err);
+        exit(1);
+    }
+    prompt::set(Some(Arc::new(cli::StdinPrompt)));
+    let config = match parse_config(read_config().expect("Error reading config")) {
+        Ok(config) => config,
+        Err(err) => {
+            output::error(&err);
+            exit(1);
+        }
+    };
     let is_link = args.link;
-    let is_force = args.force;
+    let cli_options = SyncOptions {
+        force: args.force,
+        verify: args.verify,
+        fresh: args.fresh,
+        bandwidth_limit: args.bwlimit,
+        low_priority: args.low_priority.then_some(true),
+        jobs: args.jobs,
+        bundle: args.bundle,
+        conflict: args.conflict,
+        wait: match (args.wait, args.no_wait) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        },
+        ..SyncOptions::default()
+    };
+    let start = Instant::now();
 
-    match args.cmd {
-        CliCmd::Ls(cmd_args) => ls(cmd_args, is_link, folders, links, ssh_servers),
-        CliCmd::Pull(cmd_args) => pull(
-            cmd_args,
-            is_link,
-            is_force,
-            work_folder,
-            folders,
-            links,
-            ssh_servers,
-        ),
-        CliCmd::Push(cmd_args) => push(
-            cmd_args,
-            is_link,
-            is_force,
-            work_folder,
-            folders,
-            links,
-            ssh_servers,
-        ),
+    let results: Vec<Result<Option<SyncReport>, String>> = match args.cmd {
+        CliCmd::Ls(cmd_args) => {
+            output::init(args.json, "ls");
+            ls(cmd_args, is_link, &config)
+                .into_iter()
+                .map(|result| result.map(|_| None))
+                .collect()
+        }
+        CliCmd::Pull(cmd_args) => {
+            output::init(args.json, "pull");
+            pull(cmd_args, is_link, cli_options, &config)
+                .into_iter()
+                .map(|result| result.map(Some))
+                .collect()
+        }
+        CliCmd::Push(cmd_args) => {
+            output::init(args.json, "push");
+            push(cmd_args, is_link, cli_options, &config)
+                .into_iter()
+                .map(|result| result.map(Some))
+                .collect()
+        }
+        CliCmd::Sync(cmd_args) => {
+            output::init(args.json, "sync");
+            sync(cmd_args, cli_options, &config)
+                .into_iter()
+                .map(|result| result.map(Some))
+                .collect()
+        }
+        CliCmd::Log(log_args) => {
+            output::init(args.json, "log");
+            log(log_args)
+                .into_iter()
+                .map(|result| result.map(|_| None))
+                .collect()
+        }
+        CliCmd::Watch(watch_args) => {
+            output::init(args.json, "watch");
+            watch(watch_args, cli_options, &config)
+                .into_iter()
+                .map(|result| result.map(|_| None))
+                .collect()
+        }
+        CliCmd::Daemon(daemon_args) => {
+            output::init(args.json, "daemon");
+            daemon(daemon_args, cli_options, &config)
+                .into_iter()
+                .map(|result| result.map(|_| None))
+                .collect()
+        }
+        CliCmd::Verify(cmd_args) => {
+            output::init(args.json, "verify");
+            verify(cmd_args, is_link, &config)
+                .into_iter()
+                .map(|result| result.map(|_| None))
+                .collect()
+        }
+    };
+
+    let (mut succeeded, mut failed, mut skipped, mut files, mut bytes) = (0, 0, 0, 0, 0);
+    for result in results.iter() {
+        match result {
+            Ok(Some(report)) if report.skipped => skipped += 1,
+            Ok(Some(report)) => {
+                succeeded += 1;
+                files += report.files;
+                bytes += report.bytes;
+            }
+            Ok(None) => succeeded += 1,
+            Err(err) => {
+                failed += 1;
+                output::error(err);
+            }
+        }
+    }
+    output::emit(EventKind::Summary {
+        succeeded,
+        failed,
+        skipped,
+        files,
+        bytes,
+        duration_ms: start.elapsed().as_millis(),
+    });
+    if failed > 0 {
+        exit(1);
     }
 }
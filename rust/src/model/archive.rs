@@ -0,0 +1,34 @@
+use super::compression::{Compression, CompressionKind};
+
+// What the file of an archive folder holds, told by its extension
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArchiveFormat {
+    Tar,
+    TarGz,
+    TarZst,
+    Zip,
+}
+
+impl ArchiveFormat {
+    pub fn from_path(path: &str) -> Option<Self> {
+        let format = match path {
+            path if path.ends_with(".tar") => ArchiveFormat::Tar,
+            path if path.ends_with(".tar.gz") => ArchiveFormat::TarGz,
+            path if path.ends_with(".tar.zst") => ArchiveFormat::TarZst,
+            path if path.ends_with(".zip") => ArchiveFormat::Zip,
+            _ => return None,
+        };
+        return Some(format);
+    }
+
+    // How tar compresses it, None for zip files
+    pub fn compression(&self) -> Option<Compression> {
+        let kind = match self {
+            ArchiveFormat::Tar => CompressionKind::None,
+            ArchiveFormat::TarGz => CompressionKind::Gzip,
+            ArchiveFormat::TarZst => CompressionKind::Zstd,
+            ArchiveFormat::Zip => return None,
+        };
+        return Some(Compression { kind, level: None });
+    }
+}
@@ -0,0 +1,28 @@
+use super::manifest::ManifestEntry;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Files both sides agreed on after the last successful sync, keyed by synced root
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SyncState {
+    pub roots: BTreeMap<String, BTreeMap<String, ManifestEntry>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    Push(String),
+    Pull(String),
+    DeleteLocal(String),
+    DeleteRemote(String),
+    // Moves the remote copy aside before the local one replaces it
+    RenameRemote(String, String),
+    Conflict(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum ConflictResolution {
+    Local,
+    Remote,
+    Both,
+}
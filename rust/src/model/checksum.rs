@@ -0,0 +1,28 @@
+use super::config::TomlChecksum;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChecksumAlgorithm {
+    None,
+    Sha256,
+    Blake3,
+}
+impl ChecksumAlgorithm {
+    pub fn get_checksum_algorithm(toml_checksum: TomlChecksum) -> Self {
+        let checksum_algorithm = match toml_checksum {
+            TomlChecksum::None => ChecksumAlgorithm::None,
+            TomlChecksum::Sha256 => ChecksumAlgorithm::Sha256,
+            TomlChecksum::Blake3 => ChecksumAlgorithm::Blake3,
+        };
+
+        return checksum_algorithm;
+    }
+
+    // Both print "<hash>  <path>" per file
+    pub fn program(&self) -> Option<&'static str> {
+        match self {
+            ChecksumAlgorithm::None => None,
+            ChecksumAlgorithm::Sha256 => Some("sha256sum"),
+            ChecksumAlgorithm::Blake3 => Some("b3sum"),
+        }
+    }
+}
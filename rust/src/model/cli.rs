@@ -6,8 +6,35 @@ pub struct CmdArgs {
     pub relative_path: Option<String>,
 }
 #[derive(Parser, Debug)]
+pub struct LogArgs {
+    /// Only show syncs of this link or folder
+    pub target: Option<String>,
+    /// Only show the last N entries
+    #[arg(short = 'n', long)]
+    pub limit: Option<usize>,
+}
+#[derive(Parser, Debug)]
+pub struct WatchArgs {
+    pub target: String,
+    pub relative_path: Option<String>,
+    /// Milliseconds without changes before pushing
+    #[arg(long, default_value_t = 500)]
+    pub debounce: u64,
+}
+#[derive(Parser, Debug)]
+pub struct DaemonArgs {
+    /// Print the status of the running daemon instead of starting one
+    #[arg(long)]
+    pub status: bool,
+}
+#[derive(Parser, Debug)]
 pub enum CliCmd {
     Ls(CmdArgs),
     Pull(CmdArgs),
     Push(CmdArgs),
+    Verify(CmdArgs),
+    Sync(CmdArgs),
+    Log(LogArgs),
+    Watch(WatchArgs),
+    Daemon(DaemonArgs),
 }
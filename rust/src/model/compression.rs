@@ -0,0 +1,55 @@
+use super::config::TomlCompression;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CompressionKind {
+    None,
+    Gzip,
+    Zstd,
+    Auto,
+}
+impl CompressionKind {
+    pub fn get_compression_kind(toml_compression: TomlCompression) -> Self {
+        let compression_kind = match toml_compression {
+            TomlCompression::None => CompressionKind::None,
+            TomlCompression::Gzip => CompressionKind::Gzip,
+            TomlCompression::Zstd => CompressionKind::Zstd,
+            TomlCompression::Auto => CompressionKind::Auto,
+        };
+
+        return compression_kind;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Compression {
+    pub kind: CompressionKind,
+    pub level: Option<u32>,
+}
+
+impl Compression {
+    pub fn new(toml_compression: Option<TomlCompression>, level: Option<u32>) -> Option<Self> {
+        let kind = CompressionKind::get_compression_kind(toml_compression?);
+        Some(Self { kind, level })
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self.kind {
+            CompressionKind::Gzip => "tar.gz",
+            CompressionKind::Zstd => "tar.zst",
+            CompressionKind::None | CompressionKind::Auto => "tar",
+        }
+    }
+
+    // Passed to tar as --use-compress-program, tar adds -d itself when extracting
+    pub fn program(&self, create: bool) -> Option<String> {
+        let program = match self.kind {
+            CompressionKind::Gzip => "gzip",
+            CompressionKind::Zstd => "zstd",
+            CompressionKind::None | CompressionKind::Auto => return None,
+        };
+        match (create, self.level) {
+            (true, Some(level)) => Some(format!("{} -{}", program, level)),
+            _ => Some(program.to_string()),
+        }
+    }
+}
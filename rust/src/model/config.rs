@@ -1,9 +1,20 @@
+use super::{folder::Folder, link::Link, ssh::SshServer};
 use serde::Deserialize;
 use std::collections::HashMap;
 
+// The parsed ~/.dirsync.toml every command works from
+#[derive(Debug)]
+pub struct Config {
+    pub ssh_servers: HashMap<String, SshServer>,
+    pub folders: HashMap<String, Folder>,
+    pub links: HashMap<String, Link>,
+    // Stages archives of local folders
+    pub work_folder: Option<Folder>,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct TomlConfig {
-    pub local_work_dir: String,
+    pub local_work_dir: Option<String>,
     pub folders: HashMap<String, TomlFolder>,
     pub links: HashMap<String, TomlLink>,
     pub ssh: HashMap<String, TomlSshServer>,
@@ -14,6 +25,18 @@ pub struct TomlFolder {
     pub path: String,
     pub target: TomlType,
     pub ssh_key: Option<String>,
+    // Shell commands run in the folder around every sync touching it
+    pub pre_sync: Option<String>,
+    pub post_sync: Option<String>,
+    pub pre_sync_abort: Option<bool>,
+    // Where a s3 folder keeps its objects
+    pub bucket: Option<String>,
+    pub endpoint: Option<String>,
+    pub region: Option<String>,
+    pub profile: Option<String>,
+    // Where a webdav folder lives
+    pub url: Option<String>,
+    pub netrc: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -22,14 +45,59 @@ pub struct TomlLink {
     pub target: String,
     pub paths: Vec<String>,
     pub partial_only: String,
+    pub compression: Option<TomlCompression>,
+    pub compression_level: Option<u32>,
+    pub transfer: Option<TomlTransfer>,
+    pub checksum: Option<TomlChecksum>,
+    pub verify: Option<bool>,
+    pub low_priority: Option<bool>,
+    pub jobs: Option<usize>,
+    pub bundle: Option<bool>,
+    // tar --exclude patterns
+    pub exclude: Option<Vec<String>>,
+    // Run by `dsync daemon`
+    pub schedule: Option<TomlSchedule>,
+    // pre_sync runs on the source and post_sync on the destination
+    pub pre_sync: Option<String>,
+    pub post_sync: Option<String>,
+    pub pre_sync_abort: Option<bool>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TomlSchedule {
+    // "30m", "6h", "1d"
+    pub every: Option<String>,
+    // "HH:MM" local time
+    pub at: Option<String>,
+    pub direction: Option<TomlDirection>,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct TomlSshServer {
     pub host: String,
     pub username: String,
-    pub work_dir: String,
+    pub work_dir: Option<String>,
     pub port: Option<String>,
+    pub compression: Option<TomlCompression>,
+    pub compression_level: Option<u32>,
+    // Mbit/s, used when compression is "auto"
+    pub link_speed: Option<u32>,
+    pub transfer: Option<TomlTransfer>,
+    pub checksum: Option<TomlChecksum>,
+    // Kbit/s, passed to scp -l and applied to streamed pipes
+    pub bandwidth_limit: Option<u32>,
+    pub low_priority: Option<bool>,
+    // Local directory standing in for the host, its folders live under it
+    pub loopback: Option<String>,
+    // Commands used instead of ssh and scp to reach the host
+    pub transport: Option<TomlTransport>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TomlTransport {
+    pub exec: Vec<String>,
+    pub copy: Option<Vec<String>>,
+    pub remote_path: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -38,4 +106,50 @@ pub enum TomlType {
     Local,
     #[serde(alias = "ssh")]
     Ssh,
+    #[serde(alias = "s3")]
+    S3,
+    #[serde(alias = "webdav")]
+    WebDav,
+    #[serde(alias = "archive")]
+    Archive,
+}
+
+#[derive(Deserialize, Debug)]
+pub enum TomlCompression {
+    #[serde(alias = "none")]
+    None,
+    #[serde(alias = "gzip")]
+    Gzip,
+    #[serde(alias = "zstd")]
+    Zstd,
+    #[serde(alias = "auto")]
+    Auto,
+}
+
+#[derive(Deserialize, Debug)]
+pub enum TomlTransfer {
+    #[serde(alias = "staged")]
+    Staged,
+    #[serde(alias = "stream")]
+    Stream,
+}
+
+#[derive(Deserialize, Debug)]
+pub enum TomlChecksum {
+    #[serde(alias = "none")]
+    None,
+    #[serde(alias = "sha256")]
+    Sha256,
+    #[serde(alias = "blake3")]
+    Blake3,
+}
+
+#[derive(Deserialize, Debug)]
+pub enum TomlDirection {
+    #[serde(alias = "push")]
+    Push,
+    #[serde(alias = "pull")]
+    Pull,
+    #[serde(alias = "sync")]
+    Sync,
 }
@@ -0,0 +1,105 @@
+use super::history::HistoryEntry;
+use super::manifest::DifferingEntry;
+use super::schedule::LinkStatus;
+use serde::Serialize;
+
+// Bump when a field is removed or renamed, additions keep the version
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize, Debug)]
+pub struct Event {
+    pub v: u32,
+    pub ts: String,
+    pub cmd: String,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PlanStep {
+    pub name: String,
+    pub argv: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pipe_argv: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compare_argv: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    Plan {
+        from: String,
+        to: String,
+        relative_path: Option<String>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        bundled: Vec<String>,
+        steps: Vec<PlanStep>,
+    },
+    Files {
+        path: String,
+        count: u64,
+        bytes: u64,
+    },
+    StepStart {
+        name: String,
+        argv: Vec<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pipe_argv: Option<Vec<String>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        compare_argv: Option<Vec<String>>,
+    },
+    StepFinish {
+        name: String,
+        exit_code: Option<i32>,
+        success: bool,
+        duration_ms: u128,
+    },
+    Changes {
+        local: String,
+        remote: String,
+        push: Vec<String>,
+        pull: Vec<String>,
+        delete_local: Vec<String>,
+        delete_remote: Vec<String>,
+        rename_remote: Vec<(String, String)>,
+        conflicts: Vec<String>,
+    },
+    SyncFinish {
+        from: String,
+        to: String,
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    History(HistoryEntry),
+    DaemonStatus(LinkStatus),
+    Listing {
+        path: String,
+        lines: Vec<String>,
+    },
+    Skipped {
+        from: String,
+        to: String,
+        reason: String,
+    },
+    Verify {
+        from: String,
+        to: String,
+        matched: bool,
+        missing: Vec<String>,
+        extra: Vec<String>,
+        differing: Vec<DifferingEntry>,
+    },
+    Error {
+        message: String,
+    },
+    Summary {
+        succeeded: u64,
+        failed: u64,
+        skipped: u64,
+        files: u64,
+        bytes: u64,
+        duration_ms: u128,
+    },
+}
@@ -1,24 +1,83 @@
-use super::config::TomlType;
+use super::archive::ArchiveFormat;
+use super::config::{TomlFolder, TomlType};
+use super::hook::Hooks;
+use super::s3::S3Bucket;
+use super::webdav::WebDavServer;
 
 #[derive(Clone, Debug)]
 pub enum FolderType {
     Local,
     Ssh,
+    S3(S3Bucket),
+    WebDav(WebDavServer),
+    Archive(ArchiveFormat),
 }
 impl FolderType {
-    pub fn get_folder_type(toml_type: TomlType) -> Self {
-        let folder_type = match toml_type {
+    pub fn new(name: &str, toml_folder: &TomlFolder) -> Result<Self, String> {
+        let folder_type = match toml_folder.target {
             TomlType::Local => FolderType::Local,
             TomlType::Ssh => FolderType::Ssh,
+            TomlType::S3 => FolderType::S3(
+                S3Bucket::new(toml_folder)
+                    .ok_or(format!("No S3 Bucket Found for folder {}", name))?,
+            ),
+            TomlType::WebDav => FolderType::WebDav(
+                WebDavServer::new(toml_folder)
+                    .ok_or(format!("No WebDAV Url Found for folder {}", name))?,
+            ),
+            TomlType::Archive => {
+                FolderType::Archive(ArchiveFormat::from_path(&toml_folder.path).ok_or(format!(
+                    "Archive path must end in .tar, .tar.gz, .tar.zst or .zip: {}",
+                    toml_folder.path
+                ))?)
+            }
         };
 
-        return folder_type;
+        return Ok(folder_type);
+    }
+
+    // Storage services are reached through their own client instead of a shell, archives
+    // through tar or zip
+    pub fn is_storage(&self) -> bool {
+        return matches!(
+            self,
+            FolderType::S3(_) | FolderType::WebDav(_) | FolderType::Archive(_)
+        );
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FolderType::Local => "local",
+            FolderType::Ssh => "ssh",
+            FolderType::S3(_) => "s3",
+            FolderType::WebDav(_) => "webdav",
+            FolderType::Archive(_) => "archive",
+        }
     }
 }
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub struct Folder {
     pub name: String,
     pub path: String,
     pub target: FolderType,
     pub ssh_key: Option<String>,
+    pub hooks: Hooks,
+}
+
+impl Folder {
+    pub fn new(name: String, path: String, target: FolderType, ssh_key: Option<String>) -> Self {
+        Self {
+            name,
+            path,
+            target,
+            ssh_key,
+            hooks: Hooks::default(),
+        }
+    }
+
+    pub fn hooks(mut self, hooks: Hooks) -> Self {
+        self.hooks = hooks;
+        return self;
+    }
 }
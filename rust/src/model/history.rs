@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryResult {
+    Ok,
+    Failed,
+    Skipped,
+}
+
+// One line of the history file, written when a sync of one path or bundle finished
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HistoryEntry {
+    pub time: String,
+    pub user: String,
+    pub host: String,
+    // push, pull, sync or watch
+    pub direction: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+    pub from_folder: String,
+    pub to_folder: String,
+    pub from: String,
+    pub to: String,
+    pub relative_path: Option<String>,
+    // staged, stream, bundle or bisync
+    pub mode: String,
+    pub files: u64,
+    pub bytes: u64,
+    pub duration_ms: u64,
+    pub result: HistoryResult,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
@@ -0,0 +1,43 @@
+// Shell commands run around the syncs of a link or folder
+#[derive(Clone, Debug, Default)]
+pub struct Hooks {
+    pub pre_sync: Option<String>,
+    pub post_sync: Option<String>,
+    // A failing pre_sync calls the sync off instead of only warning
+    pub pre_sync_abort: bool,
+}
+
+impl Hooks {
+    pub fn new(
+        pre_sync: Option<String>,
+        post_sync: Option<String>,
+        pre_sync_abort: Option<bool>,
+    ) -> Self {
+        Self {
+            pre_sync,
+            post_sync,
+            pre_sync_abort: pre_sync_abort.unwrap_or(true),
+        }
+    }
+
+    pub fn command(&self, stage: HookStage) -> Option<&String> {
+        match stage {
+            HookStage::PreSync => self.pre_sync.as_ref(),
+            HookStage::PostSync => self.post_sync.as_ref(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HookStage {
+    PreSync,
+    PostSync,
+}
+impl HookStage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookStage::PreSync => "pre_sync",
+            HookStage::PostSync => "post_sync",
+        }
+    }
+}
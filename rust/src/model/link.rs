@@ -1,4 +1,7 @@
-use super::folder::Folder;
+use super::{
+    checksum::ChecksumAlgorithm, compression::Compression, folder::Folder, hook::Hooks,
+    schedule::Schedule, transfer::TransferMode,
+};
 
 #[derive(Debug)]
 pub struct Link {
@@ -7,4 +10,14 @@ pub struct Link {
     pub target: Folder,
     pub paths: Vec<String>,
     pub partial_only: bool,
+    pub compression: Option<Compression>,
+    pub transfer: Option<TransferMode>,
+    pub checksum: Option<ChecksumAlgorithm>,
+    pub verify: bool,
+    pub low_priority: Option<bool>,
+    pub jobs: Option<usize>,
+    pub bundle: bool,
+    pub exclude: Vec<String>,
+    pub schedule: Option<Schedule>,
+    pub hooks: Hooks,
 }
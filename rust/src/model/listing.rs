@@ -0,0 +1,6 @@
+// One path of a folder as its endpoint lists it
+#[derive(Clone, Debug)]
+pub struct Listing {
+    pub path: String,
+    pub lines: Vec<String>,
+}
@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+// Written into the lock file, so whoever finds it knows who to ask
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LockOwner {
+    pub user: String,
+    pub host: String,
+    pub pid: u32,
+    pub since: String,
+}
@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ManifestEntry {
+    pub size: u64,
+    pub mode: String,
+    pub mtime: i64,
+    pub hash: Option<String>,
+}
+
+// Regular files keyed by their path relative to the manifest root
+#[derive(Debug, Default)]
+pub struct Manifest {
+    pub entries: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DifferingEntry {
+    pub path: String,
+    pub fields: Vec<&'static str>,
+}
+
+#[derive(Debug, Default)]
+pub struct ManifestDiff {
+    pub missing: Vec<String>,
+    pub extra: Vec<String>,
+    pub differing: Vec<DifferingEntry>,
+}
+
+impl ManifestDiff {
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.differing.is_empty()
+    }
+}
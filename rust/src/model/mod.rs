@@ -1,5 +1,23 @@
+pub mod archive;
+pub mod bisync;
+pub mod checksum;
 pub mod cli;
+pub mod compression;
 pub mod config;
+pub mod event;
 pub mod folder;
+pub mod history;
+pub mod hook;
 pub mod link;
+pub mod listing;
+pub mod lock;
+pub mod manifest;
+pub mod options;
+pub mod plan;
+pub mod report;
+pub mod s3;
+pub mod schedule;
 pub mod ssh;
+pub mod transfer;
+pub mod transport;
+pub mod webdav;
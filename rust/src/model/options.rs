@@ -0,0 +1,36 @@
+use super::{
+    bisync::ConflictResolution, checksum::ChecksumAlgorithm, compression::Compression, hook::Hooks,
+    schedule::Direction, transfer::TransferMode,
+};
+
+// Per sync settings that come from the link and the command line
+#[derive(Clone, Debug, Default)]
+pub struct SyncOptions {
+    // Name of the link being synced, recorded in the history
+    pub link: Option<String>,
+    // Recorded in the history instead of the command, set by the daemon
+    pub direction: Option<Direction>,
+    pub force: bool,
+    pub compression: Option<Compression>,
+    pub transfer: Option<TransferMode>,
+    pub checksum: Option<ChecksumAlgorithm>,
+    // Compare manifests of the source and destination trees after the sync
+    pub verify: bool,
+    // Ignore archives left by an interrupted run instead of resuming from them
+    pub fresh: bool,
+    // Kbit/s, overrides the server setting
+    pub bandwidth_limit: Option<u32>,
+    pub low_priority: Option<bool>,
+    // Paths of one link synced at the same time
+    pub jobs: Option<usize>,
+    // Pack every path of a link into one staged archive
+    pub bundle: bool,
+    // Left out of archives, manifests and watched changes
+    pub exclude: Vec<String>,
+    // The link's hooks, folder hooks come with the folders
+    pub hooks: Hooks,
+    // Wait for a locked destination instead of failing, fails when unset
+    pub wait: Option<bool>,
+    // How `sync` settles files changed on both sides, asks when unset
+    pub conflict: Option<ConflictResolution>,
+}
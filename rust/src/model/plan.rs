@@ -0,0 +1,13 @@
+use super::event::PlanStep;
+use serde::Serialize;
+
+// One path, or one bundle of paths, as it would run
+#[derive(Serialize, Clone, Debug)]
+pub struct PlannedSync {
+    pub from: String,
+    pub to: String,
+    pub relative_paths: Vec<String>,
+    pub steps: Vec<PlanStep>,
+    pub files: u64,
+    pub bytes: u64,
+}
@@ -0,0 +1,6 @@
+#[derive(Debug)]
+pub struct SyncReport {
+    pub files: u64,
+    pub bytes: u64,
+    pub skipped: bool,
+}
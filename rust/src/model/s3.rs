@@ -0,0 +1,23 @@
+use super::config::TomlFolder;
+
+// The bucket of a s3 folder, the folder path is the key prefix of its objects
+#[derive(Clone, Debug)]
+pub struct S3Bucket {
+    pub bucket: String,
+    // Any S3 compatible service, such as a MinIO server
+    pub endpoint: Option<String>,
+    pub region: Option<String>,
+    // Named aws profile holding the credentials
+    pub profile: Option<String>,
+}
+
+impl S3Bucket {
+    pub fn new(toml_folder: &TomlFolder) -> Option<Self> {
+        Some(Self {
+            bucket: toml_folder.bucket.clone()?,
+            endpoint: toml_folder.endpoint.clone(),
+            region: toml_folder.region.clone(),
+            profile: toml_folder.profile.clone(),
+        })
+    }
+}
@@ -0,0 +1,146 @@
+use super::config::{TomlDirection, TomlSchedule};
+use chrono::NaiveTime;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Push,
+    Pull,
+    Sync,
+}
+impl Direction {
+    pub fn get_direction(toml_direction: TomlDirection) -> Self {
+        let direction = match toml_direction {
+            TomlDirection::Push => Direction::Push,
+            TomlDirection::Pull => Direction::Pull,
+            TomlDirection::Sync => Direction::Sync,
+        };
+
+        return direction;
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Direction::Push => "push",
+            Direction::Pull => "pull",
+            Direction::Sync => "sync",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScheduleTime {
+    // Measured from the start of the previous run
+    Every(Duration),
+    // Local time of day
+    Daily(NaiveTime),
+}
+
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    pub time: ScheduleTime,
+    pub direction: Direction,
+    // As written in the config, shown in the status
+    pub text: String,
+}
+
+impl Schedule {
+    pub fn new(link_name: &str, toml_schedule: Option<TomlSchedule>) -> Option<Self> {
+        let toml_schedule = toml_schedule?;
+        let direction = toml_schedule
+            .direction
+            .map(Direction::get_direction)
+            .unwrap_or(Direction::Push);
+        let (time, text) = match (toml_schedule.every, toml_schedule.at) {
+            (Some(every), None) => (parse_every(&every).map(ScheduleTime::Every), every),
+            (None, Some(at)) => (
+                NaiveTime::parse_from_str(&at, "%H:%M")
+                    .ok()
+                    .map(ScheduleTime::Daily),
+                at,
+            ),
+            _ => (None, String::new()),
+        };
+        let Some(time) = time else {
+            warn!(
+                "Ignoring schedule of {}, set either every = \"<n>s|m|h|d\" or at = \"HH:MM\"",
+                link_name
+            );
+            return None;
+        };
+        let text = match time {
+            ScheduleTime::Every(_) => format!("every {}", text),
+            ScheduleTime::Daily(_) => format!("at {}", text),
+        };
+        Some(Self {
+            time,
+            direction,
+            text,
+        })
+    }
+}
+
+fn parse_every(every: &str) -> Option<Duration> {
+    let every = every.trim();
+    let unit = every.chars().last()?;
+    let count: u64 = every[..every.len() - unit.len_utf8()].trim().parse().ok()?;
+    let seconds = match unit {
+        's' => Some(count),
+        'm' => count.checked_mul(60),
+        'h' => count.checked_mul(60 * 60),
+        'd' => count.checked_mul(24 * 60 * 60),
+        _ => return None,
+    }?;
+    if seconds == 0 {
+        return None;
+    }
+    Some(Duration::from_secs(seconds))
+}
+
+// What the daemon reports over its socket for each scheduled link
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LinkStatus {
+    pub link: String,
+    pub direction: String,
+    pub schedule: String,
+    pub running: bool,
+    pub next_run: Option<String>,
+    pub last_run: Option<String>,
+    // ok, failed or skipped
+    pub last_result: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_takes_a_count_and_unit() {
+        assert_eq!(parse_every("45s"), Some(Duration::from_secs(45)));
+        assert_eq!(parse_every(" 15 m "), Some(Duration::from_secs(15 * 60)));
+        assert_eq!(parse_every("2h"), Some(Duration::from_secs(2 * 60 * 60)));
+        assert_eq!(parse_every("1d"), Some(Duration::from_secs(24 * 60 * 60)));
+    }
+
+    #[test]
+    fn invalid_every_is_rejected() {
+        for every in ["", "d", "0m", "-5m", "1.5h", "10w", "ten m", "5"] {
+            assert_eq!(parse_every(every), None, "{}", every);
+        }
+    }
+
+    #[test]
+    fn overflowing_every_is_rejected() {
+        assert_eq!(parse_every(&format!("{}d", u64::MAX / 60)), None);
+        assert_eq!(parse_every(&format!("{}h", u64::MAX)), None);
+        assert_eq!(parse_every(&format!("{}m", u64::MAX / 59)), None);
+        assert_eq!(
+            parse_every(&format!("{}s", u64::MAX)),
+            Some(Duration::from_secs(u64::MAX))
+        );
+    }
+}
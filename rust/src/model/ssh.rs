@@ -1,4 +1,7 @@
-use super::{config::TomlSshServer, folder::Folder};
+use super::{
+    checksum::ChecksumAlgorithm, compression::Compression, config::TomlSshServer, folder::Folder,
+    transfer::TransferMode, transport::Transport,
+};
 
 #[derive(Debug)]
 pub struct SshServer {
@@ -6,21 +9,47 @@ pub struct SshServer {
     pub host: String,
     pub username: String,
     pub port: u32,
-    pub work_folder: Folder,
+    pub work_folder: Option<Folder>,
+    pub compression: Option<Compression>,
+    pub link_speed: Option<u32>,
+    pub transfer: Option<TransferMode>,
+    pub checksum: Option<ChecksumAlgorithm>,
+    pub bandwidth_limit: Option<u32>,
+    pub low_priority: Option<bool>,
+    // Commands run locally and folder paths are mapped under this directory
+    pub loopback: Option<String>,
+    // Wrapper commands reaching the host in place of ssh and scp
+    pub transport: Option<Transport>,
 }
 
 impl SshServer {
-    pub fn new(key: String, toml_server: TomlSshServer, work_folder: Folder) -> Self {
+    pub fn new(
+        key: String,
+        toml_server: TomlSshServer,
+        work_folder: Option<Folder>,
+    ) -> Result<Self, String> {
         let port = match toml_server.port {
             None => 22,
-            Some(port) => port.parse::<u32>().unwrap(),
+            Some(port) => port
+                .parse::<u32>()
+                .map_err(|_| format!("Invalid port for ssh server {}: {}", key, port))?,
         };
-        Self {
+        Ok(Self {
             key,
             host: toml_server.host,
             username: toml_server.username,
             port,
             work_folder,
-        }
+            compression: Compression::new(toml_server.compression, toml_server.compression_level),
+            link_speed: toml_server.link_speed,
+            transfer: toml_server.transfer.map(TransferMode::get_transfer_mode),
+            checksum: toml_server
+                .checksum
+                .map(ChecksumAlgorithm::get_checksum_algorithm),
+            bandwidth_limit: toml_server.bandwidth_limit,
+            low_priority: toml_server.low_priority,
+            loopback: toml_server.loopback,
+            transport: toml_server.transport.map(Transport::new),
+        })
     }
 }
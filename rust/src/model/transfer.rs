@@ -0,0 +1,19 @@
+use super::config::TomlTransfer;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransferMode {
+    // Archive into the work folders, copy, then extract
+    Staged,
+    // Pipe tar straight into tar on the other side, no work folders involved
+    Stream,
+}
+impl TransferMode {
+    pub fn get_transfer_mode(toml_transfer: TomlTransfer) -> Self {
+        let transfer_mode = match toml_transfer {
+            TomlTransfer::Staged => TransferMode::Staged,
+            TomlTransfer::Stream => TransferMode::Stream,
+        };
+
+        return transfer_mode;
+    }
+}
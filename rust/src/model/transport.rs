@@ -0,0 +1,25 @@
+use super::config::TomlTransport;
+
+// Commands standing in for ssh and scp, for hosts only reached through wrappers such as
+// tsh ssh, gcloud compute ssh or kubectl exec. "{host}", "{user}" and "{port}" are filled in.
+#[derive(Clone, Debug)]
+pub struct Transport {
+    // Runs "{command}", the quoted command line, which is appended when no argument holds it
+    pub exec: Vec<String>,
+    // Copies "{from}" to "{to}", unset when files can only be streamed through exec
+    pub copy: Option<Vec<String>>,
+    // How copy names "{path}" on the host
+    pub remote_path: String,
+}
+
+impl Transport {
+    pub fn new(toml_transport: TomlTransport) -> Self {
+        Self {
+            exec: toml_transport.exec,
+            copy: toml_transport.copy,
+            remote_path: toml_transport
+                .remote_path
+                .unwrap_or("{user}@{host}:{path}".to_string()),
+        }
+    }
+}
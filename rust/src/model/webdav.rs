@@ -0,0 +1,26 @@
+use super::config::TomlFolder;
+
+// The server of a webdav folder, the folder path is appended to its url
+#[derive(Clone, Debug)]
+pub struct WebDavServer {
+    pub url: String,
+    // curl --netrc-file holding the login, ~/.netrc is read when unset
+    pub netrc: Option<String>,
+}
+
+impl WebDavServer {
+    pub fn new(toml_folder: &TomlFolder) -> Option<Self> {
+        Some(Self {
+            url: toml_folder.url.clone()?,
+            netrc: toml_folder.netrc.clone(),
+        })
+    }
+}
+
+// A directory or file found under a webdav path, relative to it
+#[derive(Clone, Debug, PartialEq)]
+pub struct WebDavEntry {
+    pub path: String,
+    pub is_dir: bool,
+    pub size: u64,
+}
@@ -0,0 +1,335 @@
+use crate::model::archive::ArchiveFormat;
+use crate::service::core::{prioritize, PullSteps, Step, SyncContext, Total};
+use crate::service::exclude;
+use crate::service::progress::{self, Measure, Probe};
+use crate::service::runner;
+use crate::service::tar::split_path;
+use std::fs;
+
+// A push writes a .part file next to the archive, which replaces it once complete
+pub fn push_steps(format: ArchiveFormat, context: &SyncContext) -> Result<Vec<Step>, String> {
+    let local_path = &context.from_folder.path;
+    let archive_path = &context.to_folder.path;
+    let part_path = format!("{}.part", archive_path);
+    // The whole folder is archived as its entries, so they are found by name
+    let members = match context.relative_paths.is_empty() {
+        true => {
+            let mut entries: Vec<String> = fs::read_dir(local_path)
+                .map_err(|err| format!("Failed to List Files: {}", err))?
+                .flatten()
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .collect();
+            entries.sort();
+            entries
+        }
+        false => context.relative_paths.clone(),
+    };
+    if members.is_empty() {
+        return Err("Error: From Folder Does Not Exist".to_string());
+    }
+    if !context.relative_paths.is_empty() {
+        keeps_every_entry(format, archive_path, &members)?;
+    }
+    let mut make_archive_dir_args: Vec<String> = Vec::new();
+    make_archive_dir_args.push("mkdir".to_string());
+    make_archive_dir_args.push("-p".to_string());
+    make_archive_dir_args.push(split_path(archive_path.clone()).0);
+    let mut delete_part_args: Vec<String> = Vec::new();
+    delete_part_args.push("rm".to_string());
+    delete_part_args.push("-f".to_string());
+    delete_part_args.push(part_path.clone());
+    let mut seal_archive_args: Vec<String> = Vec::new();
+    seal_archive_args.push("mv".to_string());
+    seal_archive_args.push(part_path.clone());
+    seal_archive_args.push(archive_path.clone());
+    let part_size_args = progress::probe_args(Measure::File, vec![part_path.clone()]);
+    return Ok(vec![
+        Step::new(
+            "make_target_dirs",
+            make_archive_dir_args,
+            "Failed to Make Target Directories",
+        ),
+        // zip adds to an archive left by an interrupted run instead of replacing it
+        Step::new(
+            "delete_part",
+            delete_part_args.clone(),
+            "Failed to Delete Partial Archive",
+        ),
+        Step::new(
+            "create_archive",
+            prioritize(
+                context,
+                create_args(format, local_path, &members, &part_path, &context.exclude),
+            ),
+            "Failed to Create Archive",
+        )
+        .with_progress(
+            "Archive",
+            Probe::Command(part_size_args, Measure::File),
+            Total::Source,
+        )
+        .with_cleanup(delete_part_args),
+        Step::new("seal_archive", seal_archive_args, "Failed to Seal Archive"),
+    ]);
+}
+
+// A push of some paths rebuilds the archive from just those, so it may not drop what else the
+// archive holds
+fn keeps_every_entry(
+    format: ArchiveFormat,
+    archive_path: &str,
+    members: &[String],
+) -> Result<(), String> {
+    if fs::metadata(archive_path).is_err() {
+        return Ok(());
+    }
+    let entries_output = runner::get()
+        .output(&entries_args(format, archive_path))
+        .map_err(|err| format!("Failed to List Archive: {}", err))?;
+    if !entries_output.status.success() {
+        return Err(format!("Failed to List Archive: {}", archive_path));
+    }
+    let entries = String::from_utf8_lossy(&entries_output.stdout);
+    let dropped = entries.lines().map(entry_path).find(|entry| {
+        !entry.is_empty()
+            && !members.iter().any(|member| {
+                entry == member
+                    || entry.starts_with(&format!("{}/", member))
+                    || member.starts_with(&format!("{}/", entry))
+            })
+    });
+    return match dropped {
+        Some(entry) => Err(format!(
+            "{} also holds {}, push the whole folder to replace it",
+            archive_path, entry
+        )),
+        None => Ok(()),
+    };
+}
+
+// A pull extracts the members into the local folder, counted from the entries of the archive
+pub fn pull_steps(format: ArchiveFormat, context: &SyncContext) -> Result<PullSteps, String> {
+    let archive_path = &context.from_folder.path;
+    let members = &context.relative_paths;
+    let missing = "Error: From Folder Does Not Exist".to_string();
+    let entries_output = runner::get()
+        .output(&entries_args(format, archive_path))
+        .map_err(|err| format!("Failed to List Archive: {}", err))?;
+    if !entries_output.status.success() {
+        return Err(missing);
+    }
+    let entries = String::from_utf8_lossy(&entries_output.stdout);
+    let patterns = member_patterns(format, members, &entries).ok_or(missing)?;
+    let files = entries
+        .lines()
+        .filter(|entry| !entry.ends_with('/'))
+        .filter(|entry| {
+            members.is_empty()
+                || members.iter().any(|member| {
+                    let entry = entry.trim_start_matches("./");
+                    entry == member || entry.starts_with(&format!("{}/", member))
+                })
+        })
+        .count() as u64;
+    let bytes = fs::metadata(archive_path)
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+
+    let local_path = &context.to_folder.path;
+    let targets: Vec<String> = match members.is_empty() {
+        true => vec![local_path.clone()],
+        false => members
+            .iter()
+            .map(|member| format!("{}/{}", local_path, member))
+            .collect(),
+    };
+    let tree_size_args = progress::probe_args(Measure::Tree, targets.clone());
+    let steps = vec![
+        Step::new(
+            "delete_target",
+            context.to.remove(&targets)?,
+            "Failed to Delete Target Folder",
+        ),
+        Step::new(
+            "make_target_dirs",
+            context.to.mkdir(std::slice::from_ref(local_path))?,
+            "Failed to Make Target Directories",
+        ),
+        Step::new(
+            "extract",
+            prioritize(
+                context,
+                extract_args(
+                    format,
+                    archive_path,
+                    local_path,
+                    &patterns,
+                    &context.exclude,
+                ),
+            ),
+            "Failed to Extract Archive",
+        )
+        .with_progress(
+            "Extract",
+            Probe::Command(tree_size_args, Measure::Tree),
+            Total::Source,
+        ),
+    ];
+    return Ok((steps, files, bytes));
+}
+
+// zip has no -C, so it is run from base_path
+pub fn create_args(
+    format: ArchiveFormat,
+    base_path: &str,
+    members: &[String],
+    archive_path: &str,
+    exclude: &[String],
+) -> Vec<String> {
+    let mut create_args: Vec<String> = Vec::new();
+    match format.compression() {
+        Some(compression) => {
+            create_args.push("tar".to_string());
+            if let Some(program) = compression.program(true) {
+                create_args.push(format!("--use-compress-program={}", program));
+            }
+            create_args.extend(exclude::tar_args(exclude));
+            create_args.push("-cf".to_string());
+            create_args.push(archive_path.to_string());
+            create_args.push("-C".to_string());
+            create_args.push(base_path.to_string());
+            create_args.extend(members.iter().cloned());
+        }
+        None => {
+            create_args.push("sh".to_string());
+            create_args.push("-c".to_string());
+            create_args.push("cd \"$1\" && shift && exec \"$@\"".to_string());
+            create_args.push("sh".to_string());
+            create_args.push(base_path.to_string());
+            create_args.push("zip".to_string());
+            create_args.push("-q".to_string());
+            create_args.push("-r".to_string());
+            create_args.push(archive_path.to_string());
+            create_args.extend(members.iter().cloned());
+            create_args.extend(zip_exclude_args(exclude));
+        }
+    }
+    return create_args;
+}
+
+pub fn extract_args(
+    format: ArchiveFormat,
+    archive_path: &str,
+    base_path: &str,
+    patterns: &[String],
+    exclude: &[String],
+) -> Vec<String> {
+    let mut extract_args: Vec<String> = Vec::new();
+    match format.compression() {
+        Some(compression) => {
+            extract_args.push("tar".to_string());
+            if let Some(program) = compression.program(false) {
+                extract_args.push(format!("--use-compress-program={}", program));
+            }
+            extract_args.extend(exclude::tar_args(exclude));
+            extract_args.push("-xf".to_string());
+            extract_args.push(archive_path.to_string());
+            extract_args.push("-C".to_string());
+            extract_args.push(base_path.to_string());
+            extract_args.extend(patterns.iter().cloned());
+        }
+        None => {
+            extract_args.push("unzip".to_string());
+            extract_args.push("-q".to_string());
+            extract_args.push("-o".to_string());
+            extract_args.push(archive_path.to_string());
+            extract_args.extend(patterns.iter().cloned());
+            extract_args.extend(zip_exclude_args(exclude));
+            extract_args.push("-d".to_string());
+            extract_args.push(base_path.to_string());
+        }
+    }
+    return extract_args;
+}
+
+pub fn entries_args(format: ArchiveFormat, archive_path: &str) -> Vec<String> {
+    let mut entries_args: Vec<String> = Vec::new();
+    match format.compression() {
+        Some(compression) => {
+            entries_args.push("tar".to_string());
+            if let Some(program) = compression.program(false) {
+                entries_args.push(format!("--use-compress-program={}", program));
+            }
+            entries_args.push("-tf".to_string());
+            entries_args.push(archive_path.to_string());
+        }
+        None => {
+            entries_args.push("unzip".to_string());
+            entries_args.push("-Z1".to_string());
+            entries_args.push(archive_path.to_string());
+        }
+    }
+    return entries_args;
+}
+
+// None when a member is not in the archive, unzip fails on patterns matching nothing
+pub fn member_patterns(
+    format: ArchiveFormat,
+    members: &[String],
+    entries: &str,
+) -> Option<Vec<String>> {
+    let mut patterns: Vec<String> = Vec::new();
+    for member in members {
+        let is_file = entries.lines().any(|entry| entry_path(entry) == member);
+        let is_dir = entries
+            .lines()
+            .any(|entry| entry_path(entry).starts_with(&format!("{}/", member)));
+        match (format, is_file, is_dir) {
+            (_, false, false) => return None,
+            (ArchiveFormat::Zip, _, true) => patterns.push(format!("{}/*", member)),
+            _ => patterns.push(member.clone()),
+        }
+    }
+    return Some(patterns);
+}
+
+pub fn listing(entries: &str, member: Option<&str>) -> String {
+    return entries
+        .lines()
+        .filter_map(|entry| {
+            let entry = entry.trim_start_matches("./");
+            if member == Some(entry) {
+                return Some(entry.to_string());
+            }
+            let name = match member {
+                Some(member) => entry.strip_prefix(member)?.strip_prefix('/')?,
+                None => entry,
+            };
+            match name.is_empty() {
+                true => None,
+                false => Some(name.to_string()),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+}
+
+fn entry_path(entry: &str) -> &str {
+    return entry.trim_start_matches("./").trim_end_matches('/');
+}
+
+// zip patterns match the whole entry path, unlike tar --exclude
+fn zip_exclude_args(patterns: &[String]) -> Vec<String> {
+    let mut exclude_args: Vec<String> = Vec::new();
+    if patterns.is_empty() {
+        return exclude_args;
+    }
+    exclude_args.push("-x".to_string());
+    for pattern in patterns {
+        exclude_args.push(pattern.clone());
+        exclude_args.push(format!("*/{}", pattern));
+        exclude_args.push(format!("{}/*", pattern));
+        exclude_args.push(format!("*/{}/*", pattern));
+    }
+    return exclude_args;
+}
@@ -0,0 +1,662 @@
+use crate::model::{
+    bisync::{Change, ConflictResolution, SyncState},
+    checksum::ChecksumAlgorithm,
+    event::EventKind,
+    folder::Folder,
+    hook::HookStage,
+    link::Link,
+    manifest::{Manifest, ManifestEntry},
+    options::SyncOptions,
+    report::SyncReport,
+    ssh::SshServer,
+};
+use crate::service::checksum;
+use crate::service::core::{self, build_path};
+use crate::service::endpoint;
+use crate::service::history;
+use crate::service::hook;
+use crate::service::lock;
+use crate::service::manifest;
+use crate::service::output;
+use crate::service::prompt;
+use crate::service::runner;
+use home::home_dir;
+use log::{debug, info, warn};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+fn state_path(link_name: &str) -> PathBuf {
+    let home_dir = home_dir().expect("No Home Dir");
+    return Path::new(&home_dir.as_os_str())
+        .join(".dirsync_state")
+        .join(format!("{}.json", link_name));
+}
+
+pub fn load_state(link_name: &str) -> SyncState {
+    let path = state_path(link_name);
+    let Ok(file) = fs::read_to_string(&path) else {
+        return SyncState::default();
+    };
+    return serde_json::from_str(&file).unwrap_or_else(|err| {
+        warn!("Ignoring unreadable sync state {}: {}", path.display(), err);
+        SyncState::default()
+    });
+}
+
+pub fn save_state(link_name: &str, state: &SyncState) -> Result<(), String> {
+    let path = state_path(link_name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| format!("Failed to Save State: {}", err))?;
+    }
+    let state = serde_json::to_string_pretty(state).expect("Error serializing state");
+    return fs::write(&path, state).map_err(|err| format!("Failed to Save State: {}", err));
+}
+
+// A root missing on one side is treated as empty, the sync creates it. Only test answering 1
+// means missing, an unreachable host would otherwise look like every file was deleted.
+fn scan(
+    folder: &Folder,
+    path: &str,
+    ssh_servers: &HashMap<String, SshServer>,
+    checksum_algorithm: ChecksumAlgorithm,
+    exclude: &[String],
+) -> Result<Manifest, String> {
+    let mut exists_args: Vec<String> = Vec::new();
+    exists_args.push("test".to_string());
+    exists_args.push("-e".to_string());
+    exists_args.push(path.to_string());
+    let exists_args = endpoint::get(folder, ssh_servers)?.exec(exists_args)?;
+    let exists = runner::get()
+        .output(&exists_args)
+        .map_err(|err| format!("Failed to Check Folder: {}", err))?;
+    match exists.status.code() {
+        Some(0) => (),
+        Some(1) => {
+            debug!("{} does not exist yet", path);
+            return Ok(Manifest::default());
+        }
+        _ => {
+            return Err(format!(
+                "Failed to Check Folder: {}: {}",
+                path,
+                String::from_utf8_lossy(&exists.stderr).trim()
+            ));
+        }
+    }
+    let manifest = manifest::build(folder, path, ssh_servers, checksum_algorithm)?;
+    return Ok(manifest::exclude(manifest, exclude));
+}
+
+fn hash_of(entries: &BTreeMap<String, ManifestEntry>, path: &str) -> Option<String> {
+    return entries.get(path).and_then(|entry| entry.hash.clone());
+}
+
+// Three way comparison of each side against the state of the last sync
+pub fn plan_changes(
+    local: &Manifest,
+    remote: &Manifest,
+    base: &BTreeMap<String, ManifestEntry>,
+) -> Vec<Change> {
+    let mut paths: BTreeSet<&String> = BTreeSet::new();
+    paths.extend(local.entries.keys());
+    paths.extend(remote.entries.keys());
+    paths.extend(base.keys());
+
+    let mut changes = Vec::new();
+    for path in paths {
+        let local_hash = hash_of(&local.entries, path);
+        let remote_hash = hash_of(&remote.entries, path);
+        if local_hash == remote_hash {
+            continue;
+        }
+        let base_hash = hash_of(base, path);
+        let change = match (local_hash != base_hash, remote_hash != base_hash) {
+            (true, false) if local_hash.is_some() => Change::Push(path.clone()),
+            (true, false) => Change::DeleteRemote(path.clone()),
+            (false, true) if remote_hash.is_some() => Change::Pull(path.clone()),
+            (false, true) => Change::DeleteLocal(path.clone()),
+            _ => Change::Conflict(path.clone()),
+        };
+        changes.push(change);
+    }
+    return changes;
+}
+
+// A deletion on one side against an edit on the other keeps the edit for "both"
+pub fn resolve_conflict(
+    path: &str,
+    local: &Manifest,
+    remote: &Manifest,
+    resolution: ConflictResolution,
+) -> Vec<Change> {
+    let in_local = local.entries.contains_key(path);
+    let in_remote = remote.entries.contains_key(path);
+    let path = path.to_string();
+    return match resolution {
+        ConflictResolution::Local if in_local => vec![Change::Push(path)],
+        ConflictResolution::Local => vec![Change::DeleteRemote(path)],
+        ConflictResolution::Remote if in_remote => vec![Change::Pull(path)],
+        ConflictResolution::Remote => vec![Change::DeleteLocal(path)],
+        ConflictResolution::Both if in_local && in_remote => {
+            let remote_copy = format!("{}.remote", path);
+            vec![
+                Change::RenameRemote(path.clone(), remote_copy.clone()),
+                Change::Push(path),
+                Change::Pull(remote_copy),
+            ]
+        }
+        ConflictResolution::Both if in_local => vec![Change::Push(path)],
+        ConflictResolution::Both => vec![Change::Pull(path)],
+    };
+}
+
+pub fn sync(
+    link: &Link,
+    work_folder: &Option<Folder>,
+    ssh_servers: &HashMap<String, SshServer>,
+    relative_path: &Option<String>,
+    options: &SyncOptions,
+) -> Vec<Result<SyncReport, String>> {
+    if let Some(storage) = [&link.local.target, &link.target.target]
+        .into_iter()
+        .find(|target| target.is_storage())
+    {
+        return vec![Err(format!(
+            "Two way syncs are not supported for {} folders",
+            storage.name()
+        ))];
+    }
+    let roots: Vec<Option<String>> = if relative_path.is_some() || link.paths.is_empty() {
+        vec![relative_path.clone()]
+    } else {
+        link.paths.iter().cloned().map(Some).collect()
+    };
+
+    // The local side counts as the source for hooks
+    if let Err(err) = hook::run(
+        HookStage::PreSync,
+        &link.local,
+        &link.target,
+        ssh_servers,
+        &roots,
+        options,
+    ) {
+        return vec![Err(err)];
+    }
+    let mut results = Vec::new();
+    for root in roots.iter() {
+        let start = Instant::now();
+        // Both sides are written to, so both are locked
+        let locks: Result<Vec<(&Folder, String)>, String> = [&link.local, &link.target]
+            .into_iter()
+            .map(|folder| {
+                let path = build_path(folder, root);
+                Ok((
+                    folder,
+                    lock::lock_path(folder, &path, work_folder, ssh_servers)?,
+                ))
+            })
+            .collect();
+        let locks = locks.and_then(|locks| {
+            lock::acquire_all(&locks, ssh_servers, options.wait.unwrap_or(false))?;
+            Ok(locks)
+        });
+        let result = match locks {
+            Ok(locks) => {
+                // Read under the lock, another sync of this link may just have saved it
+                let mut state = load_state(&link.name);
+                let loaded = state.clone();
+                let mut result = sync_root(link, ssh_servers, root, &mut state, options);
+                // A root left with conflicts still records the files that did settle, one that
+                // failed before syncing leaves the state as it was
+                if state != loaded {
+                    if let Err(err) = save_state(&link.name, &state) {
+                        result = Err(err);
+                    }
+                }
+                lock::release_all(&locks, ssh_servers);
+                result
+            }
+            Err(err) => Err(err),
+        };
+        history::record(
+            (&link.local, build_path(&link.local, root)),
+            (&link.target, build_path(&link.target, root)),
+            root.clone(),
+            "bisync",
+            options,
+            &result,
+            start.elapsed().as_millis() as u64,
+        );
+        results.push(result);
+    }
+    if results
+        .iter()
+        .all(|result| matches!(result, Ok(report) if !report.skipped))
+    {
+        if let Err(err) = hook::run(
+            HookStage::PostSync,
+            &link.local,
+            &link.target,
+            ssh_servers,
+            &roots,
+            options,
+        ) {
+            results.push(Err(err));
+        }
+    }
+    return results;
+}
+
+fn sync_root(
+    link: &Link,
+    ssh_servers: &HashMap<String, SshServer>,
+    root: &Option<String>,
+    state: &mut SyncState,
+    options: &SyncOptions,
+) -> Result<SyncReport, String> {
+    let (local_folder, remote_folder) = (&link.local, &link.target);
+    let local_path = build_path(local_folder, root);
+    let remote_path = build_path(remote_folder, root);
+    info!("Sync: {} <-> {}", local_path, remote_path);
+
+    // Changes are told apart by hash, so one is needed even with checksum = "none"
+    let checksum_algorithm =
+        match checksum::resolve(&options.checksum, local_folder, remote_folder, ssh_servers) {
+            ChecksumAlgorithm::None => ChecksumAlgorithm::Sha256,
+            checksum_algorithm => checksum_algorithm,
+        };
+    let local = scan(
+        local_folder,
+        &local_path,
+        ssh_servers,
+        checksum_algorithm,
+        &options.exclude,
+    )?;
+    let remote = scan(
+        remote_folder,
+        &remote_path,
+        ssh_servers,
+        checksum_algorithm,
+        &options.exclude,
+    )?;
+    let state_key = root.clone().unwrap_or_default();
+    let base = state.roots.get(&state_key).cloned().unwrap_or_default();
+
+    let mut changes = Vec::new();
+    let mut conflicts = Vec::new();
+    for change in plan_changes(&local, &remote, &base) {
+        let Change::Conflict(path) = change else {
+            changes.push(change);
+            continue;
+        };
+        let resolution = match options.conflict {
+            Some(resolution) => Some(resolution),
+            None if options.force || output::is_json() => None,
+            None => prompt::get().and_then(|prompt| prompt.resolve_conflict(&path)),
+        };
+        match resolution {
+            Some(resolution) => {
+                info!("Conflict: {} resolved with {:?}", path, resolution);
+                changes.extend(resolve_conflict(&path, &local, &remote, resolution));
+            }
+            None => {
+                warn!("Conflict: {} changed on both sides, left as is", path);
+                conflicts.push(path);
+            }
+        }
+    }
+
+    let (mut push, mut pull, mut delete_local, mut delete_remote, mut rename_remote) =
+        (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for change in changes {
+        match change {
+            Change::Push(path) => push.push(path),
+            Change::Pull(path) => pull.push(path),
+            Change::DeleteLocal(path) => delete_local.push(path),
+            Change::DeleteRemote(path) => delete_remote.push(path),
+            Change::RenameRemote(from, to) => rename_remote.push((from, to)),
+            Change::Conflict(path) => conflicts.push(path),
+        }
+    }
+    output::emit(EventKind::Changes {
+        local: local_path.clone(),
+        remote: remote_path.clone(),
+        push: push.clone(),
+        pull: pull.clone(),
+        delete_local: delete_local.clone(),
+        delete_remote: delete_remote.clone(),
+        rename_remote: rename_remote.clone(),
+        conflicts: conflicts.clone(),
+    });
+
+    let files = (push.len() + pull.len()) as u64;
+    let bytes = push
+        .iter()
+        .filter_map(|path| local.entries.get(path))
+        .chain(pull.iter().filter_map(|path| remote.entries.get(path)))
+        .map(|entry| entry.size)
+        .sum();
+    let is_empty = push.is_empty()
+        && pull.is_empty()
+        && delete_local.is_empty()
+        && delete_remote.is_empty()
+        && rename_remote.is_empty();
+    if is_empty {
+        info!("Nothing to sync");
+    } else {
+        info!("Ready for sync, would you like to continue? The following changes will be made");
+        for (from, to) in rename_remote.iter() {
+            info!("- rename remote {} to {}", from, to);
+        }
+        for path in delete_local.iter() {
+            info!("- delete local {}", path);
+        }
+        for path in delete_remote.iter() {
+            info!("- delete remote {}", path);
+        }
+        for path in push.iter() {
+            info!("- push {}", path);
+        }
+        for path in pull.iter() {
+            info!("- pull {}", path);
+        }
+        if !options.force && !core::confirm() {
+            let reason = "Skipping this folder because the user did not input 'y'";
+            info!("{}", reason);
+            output::emit(EventKind::Skipped {
+                from: local_path,
+                to: remote_path,
+                reason: reason.to_string(),
+            });
+            return Ok(SyncReport {
+                files,
+                bytes,
+                skipped: true,
+            });
+        }
+    }
+
+    for (from, to) in rename_remote.iter() {
+        core::run(
+            "rename_remote",
+            endpoint::get(remote_folder, ssh_servers)?.rename(
+                &format!("{}/{}", remote_path, from),
+                &format!("{}/{}", remote_path, to),
+            )?,
+            "Failed to Rename Remote File",
+        )?;
+    }
+    for batch in runner::batches(&delete_local, |path| path.len() + 1) {
+        core::run(
+            "delete_local",
+            endpoint::get(local_folder, ssh_servers)?.exec(delete_args(&local_path, batch))?,
+            "Failed to Delete Local Files",
+        )?;
+    }
+    for batch in runner::batches(&delete_remote, |path| path.len() + 1) {
+        core::run(
+            "delete_remote",
+            endpoint::get(remote_folder, ssh_servers)?.exec(delete_args(&remote_path, batch))?,
+            "Failed to Delete Remote Files",
+        )?;
+    }
+    if !push.is_empty() {
+        copy_files(
+            "push",
+            (local_folder, &local_path),
+            (remote_folder, &remote_path),
+            &push,
+            ssh_servers,
+        )?;
+    }
+    if !pull.is_empty() {
+        copy_files(
+            "pull",
+            (remote_folder, &remote_path),
+            (local_folder, &local_path),
+            &pull,
+            ssh_servers,
+        )?;
+    }
+
+    // Only files both sides now agree on become the base, unresolved conflicts stay conflicts
+    let local = scan(
+        local_folder,
+        &local_path,
+        ssh_servers,
+        checksum_algorithm,
+        &options.exclude,
+    )?;
+    let remote = scan(
+        remote_folder,
+        &remote_path,
+        ssh_servers,
+        checksum_algorithm,
+        &options.exclude,
+    )?;
+    let agreed = local
+        .entries
+        .into_iter()
+        .filter(|(path, entry)| {
+            entry.hash.is_some() && hash_of(&remote.entries, path) == entry.hash
+        })
+        .collect();
+    state.roots.insert(state_key, agreed);
+
+    if !conflicts.is_empty() {
+        return Err(format!(
+            "{} conflicts left unresolved in {}",
+            conflicts.len(),
+            local_path
+        ));
+    }
+    return Ok(SyncReport {
+        files,
+        bytes,
+        skipped: false,
+    });
+}
+
+// Directories left empty by the deletes go as well, up to the root
+const DELETE_SCRIPT: &str = r#"cd "$1" || exit 1
+shift
+rm -f -- "$@" || exit 1
+for path in "$@"; do
+    rmdir -p -- "$(dirname -- "$path")" 2>/dev/null
+done
+exit 0"#;
+
+fn delete_args(root: &str, paths: &[String]) -> Vec<String> {
+    let mut delete_args: Vec<String> = Vec::new();
+    delete_args.push("sh".to_string());
+    delete_args.push("-c".to_string());
+    delete_args.push(DELETE_SCRIPT.to_string());
+    delete_args.push("sh".to_string());
+    delete_args.push(root.to_string());
+    delete_args.extend(paths.iter().cloned());
+    return delete_args;
+}
+
+// Streams just the listed files with tar, keeping their modes and mtimes, in batches that keep
+// the command line short
+fn copy_files(
+    name: &'static str,
+    (from_folder, from_root): (&Folder, &str),
+    (to_folder, to_root): (&Folder, &str),
+    paths: &[String],
+    ssh_servers: &HashMap<String, SshServer>,
+) -> Result<(), String> {
+    core::run(
+        "make_target_dirs",
+        endpoint::get(to_folder, ssh_servers)?.mkdir(&[to_root.to_string()])?,
+        "Failed to Make Target Directories",
+    )?;
+
+    for batch in runner::batches(paths, |path| path.len() + 1) {
+        let mut create_tar_args: Vec<String> = Vec::new();
+        create_tar_args.push("tar".to_string());
+        create_tar_args.push("-cf".to_string());
+        create_tar_args.push("-".to_string());
+        create_tar_args.push("-C".to_string());
+        create_tar_args.push(from_root.to_string());
+        create_tar_args.extend(batch.iter().cloned());
+
+        let mut untar_args: Vec<String> = Vec::new();
+        untar_args.push("tar".to_string());
+        untar_args.push("-xf".to_string());
+        untar_args.push("-".to_string());
+        untar_args.push("-C".to_string());
+        untar_args.push(to_root.to_string());
+
+        core::pipe(
+            name,
+            endpoint::get(from_folder, ssh_servers)?.exec(create_tar_args)?,
+            endpoint::get(to_folder, ssh_servers)?.exec(untar_args)?,
+            "Failed to Copy Files",
+        )?;
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(hash: &str) -> ManifestEntry {
+        return ManifestEntry {
+            size: 1,
+            mode: "644".to_string(),
+            mtime: 1,
+            hash: Some(hash.to_string()),
+        };
+    }
+
+    fn manifest(files: &[(&str, &str)]) -> Manifest {
+        return Manifest {
+            entries: files
+                .iter()
+                .map(|(path, hash)| (path.to_string(), entry(hash)))
+                .collect(),
+        };
+    }
+
+    fn changes(
+        local: &[(&str, &str)],
+        remote: &[(&str, &str)],
+        base: &[(&str, &str)],
+    ) -> Vec<Change> {
+        return plan_changes(&manifest(local), &manifest(remote), &manifest(base).entries);
+    }
+
+    #[test]
+    fn matching_sides_need_nothing() {
+        assert!(changes(&[("a", "1")], &[("a", "1")], &[("a", "1")]).is_empty());
+        // Both made the same edit, or both added the same file
+        assert!(changes(&[("a", "2")], &[("a", "2")], &[("a", "1")]).is_empty());
+        assert!(changes(&[("a", "1")], &[("a", "1")], &[]).is_empty());
+        // Both deleted it
+        assert!(changes(&[], &[], &[("a", "1")]).is_empty());
+    }
+
+    #[test]
+    fn a_change_on_one_side_is_copied_to_the_other() {
+        let push = vec![Change::Push("a".to_string())];
+        let pull = vec![Change::Pull("a".to_string())];
+        assert_eq!(changes(&[("a", "2")], &[("a", "1")], &[("a", "1")]), push);
+        assert_eq!(changes(&[("a", "1")], &[], &[]), push);
+        assert_eq!(changes(&[("a", "1")], &[("a", "2")], &[("a", "1")]), pull);
+        assert_eq!(changes(&[], &[("a", "1")], &[]), pull);
+    }
+
+    #[test]
+    fn a_deletion_on_one_side_is_deleted_on_the_other() {
+        assert_eq!(
+            changes(&[], &[("a", "1")], &[("a", "1")]),
+            vec![Change::DeleteRemote("a".to_string())]
+        );
+        assert_eq!(
+            changes(&[("a", "1")], &[], &[("a", "1")]),
+            vec![Change::DeleteLocal("a".to_string())]
+        );
+    }
+
+    #[test]
+    fn changes_on_both_sides_conflict() {
+        let conflict = vec![Change::Conflict("a".to_string())];
+        // Edited differently on each side
+        assert_eq!(
+            changes(&[("a", "2")], &[("a", "3")], &[("a", "1")]),
+            conflict
+        );
+        // Added differently on each side
+        assert_eq!(changes(&[("a", "2")], &[("a", "3")], &[]), conflict);
+        // Edited on one side, deleted on the other
+        assert_eq!(changes(&[("a", "2")], &[], &[("a", "1")]), conflict);
+        assert_eq!(changes(&[], &[("a", "2")], &[("a", "1")]), conflict);
+    }
+
+    #[test]
+    fn every_path_gets_its_own_change() {
+        let planned = changes(
+            &[("edited", "2"), ("same", "1"), ("new", "1")],
+            &[("edited", "1"), ("same", "1"), ("theirs", "1")],
+            &[("edited", "1"), ("same", "1"), ("removed", "1")],
+        );
+        assert_eq!(
+            planned,
+            vec![
+                Change::Push("edited".to_string()),
+                Change::Push("new".to_string()),
+                Change::Pull("theirs".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn conflicts_resolve_to_the_chosen_side() {
+        let local = manifest(&[("a", "2")]);
+        let remote = manifest(&[("a", "3")]);
+        assert_eq!(
+            resolve_conflict("a", &local, &remote, ConflictResolution::Local),
+            vec![Change::Push("a".to_string())]
+        );
+        assert_eq!(
+            resolve_conflict("a", &local, &remote, ConflictResolution::Remote),
+            vec![Change::Pull("a".to_string())]
+        );
+        assert_eq!(
+            resolve_conflict("a", &local, &remote, ConflictResolution::Both),
+            vec![
+                Change::RenameRemote("a".to_string(), "a.remote".to_string()),
+                Change::Push("a".to_string()),
+                Change::Pull("a.remote".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_deletion_against_an_edit_follows_the_chosen_side() {
+        let edited = manifest(&[("a", "2")]);
+        let deleted = manifest(&[]);
+        assert_eq!(
+            resolve_conflict("a", &deleted, &edited, ConflictResolution::Local),
+            vec![Change::DeleteRemote("a".to_string())]
+        );
+        assert_eq!(
+            resolve_conflict("a", &edited, &deleted, ConflictResolution::Remote),
+            vec![Change::DeleteLocal("a".to_string())]
+        );
+        // Both keeps the edit wherever it was made
+        assert_eq!(
+            resolve_conflict("a", &deleted, &edited, ConflictResolution::Both),
+            vec![Change::Pull("a".to_string())]
+        );
+        assert_eq!(
+            resolve_conflict("a", &edited, &deleted, ConflictResolution::Both),
+            vec![Change::Push("a".to_string())]
+        );
+    }
+}
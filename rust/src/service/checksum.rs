@@ -0,0 +1,54 @@
+use crate::model::{checksum::ChecksumAlgorithm, folder::Folder, ssh::SshServer};
+use crate::service::exclude;
+use std::collections::HashMap;
+
+// The link setting wins over the server, nothing is hashed by default
+pub fn resolve(
+    link_checksum: &Option<ChecksumAlgorithm>,
+    from_folder: &Folder,
+    to_folder: &Folder,
+    ssh_servers: &HashMap<String, SshServer>,
+) -> ChecksumAlgorithm {
+    let ssh_server = crate::service::ssh::get_remote(from_folder, to_folder, ssh_servers);
+    return link_checksum
+        .or_else(|| ssh_server.and_then(|ssh_server| ssh_server.checksum))
+        .unwrap_or(ChecksumAlgorithm::None);
+}
+
+pub fn file_args(program: &str, path: String) -> Vec<String> {
+    let mut checksum_args: Vec<String> = Vec::new();
+    checksum_args.push(program.to_string());
+    checksum_args.push(path);
+    return checksum_args;
+}
+
+pub fn tree_args(program: &str, path: String) -> Vec<String> {
+    let mut checksum_args: Vec<String> = Vec::new();
+    checksum_args.push("find".to_string());
+    checksum_args.push(path);
+    checksum_args.push("-type".to_string());
+    checksum_args.push("f".to_string());
+    checksum_args.push("-exec".to_string());
+    checksum_args.push(program.to_string());
+    checksum_args.push("{}".to_string());
+    checksum_args.push("+".to_string());
+    return checksum_args;
+}
+
+// "<hash>  <root>/<rel>" lines become sorted "<hash>  <rel>" so both sides compare equal
+pub fn normalize(checksum_output: &str, root: &str, exclude: &[String]) -> Vec<String> {
+    let mut lines: Vec<String> = checksum_output
+        .lines()
+        .filter_map(|line| {
+            let (hash, path) = line.split_once("  ")?;
+            let path = path.strip_prefix(root).unwrap_or(path);
+            let path = path.trim_start_matches('/');
+            if exclude::is_excluded(path, exclude) {
+                return None;
+            }
+            Some(format!("{}  {}", hash, path))
+        })
+        .collect();
+    lines.sort();
+    return lines;
+}
@@ -0,0 +1,61 @@
+use crate::model::{
+    compression::{Compression, CompressionKind},
+    folder::{Folder, FolderType},
+    ssh::SshServer,
+};
+use std::collections::HashMap;
+
+// Above this the CPU spent compressing costs more than the bytes saved
+const FAST_LINK_MBPS: u32 = 1000;
+const SLOW_LINK_MBPS: u32 = 100;
+
+fn auto(is_remote: bool, link_speed: Option<u32>, level: Option<u32>) -> Compression {
+    if !is_remote {
+        return Compression {
+            kind: CompressionKind::None,
+            level: None,
+        };
+    }
+    return match link_speed {
+        Some(speed) if speed >= FAST_LINK_MBPS => Compression {
+            kind: CompressionKind::None,
+            level: None,
+        },
+        Some(speed) if speed < SLOW_LINK_MBPS => Compression {
+            kind: CompressionKind::Zstd,
+            level: level.or(Some(9)),
+        },
+        _ => Compression {
+            kind: CompressionKind::Zstd,
+            level: level.or(Some(3)),
+        },
+    };
+}
+
+// The link setting wins over the server, "auto" picks from the link speed and nothing leaves
+// archives uncompressed
+pub fn resolve(
+    link_compression: &Option<Compression>,
+    from_folder: &Folder,
+    to_folder: &Folder,
+    ssh_servers: &HashMap<String, SshServer>,
+) -> Compression {
+    let ssh_server = crate::service::ssh::get_remote(from_folder, to_folder, ssh_servers);
+    // Buckets are across a network of unknown speed
+    let is_remote = ssh_server.is_some()
+        || [from_folder, to_folder]
+            .iter()
+            .any(|folder| matches!(folder.target, FolderType::S3(_)));
+    let link_speed = ssh_server.and_then(|ssh_server| ssh_server.link_speed);
+    let configured = link_compression
+        .clone()
+        .or_else(|| ssh_server.and_then(|ssh_server| ssh_server.compression.clone()));
+    return match configured {
+        Some(compression) if compression.kind != CompressionKind::Auto => compression,
+        Some(compression) => auto(is_remote, link_speed, compression.level),
+        None => Compression {
+            kind: CompressionKind::None,
+            level: None,
+        },
+    };
+}
@@ -1,8 +1,13 @@
-use crate::model::config::TomlConfig;
+use crate::model::checksum::ChecksumAlgorithm;
+use crate::model::compression::Compression;
+use crate::model::config::{Config, TomlConfig, TomlType};
 use crate::model::folder::Folder;
 use crate::model::folder::FolderType;
+use crate::model::hook::Hooks;
 use crate::model::link::Link;
+use crate::model::schedule::Schedule;
 use crate::model::ssh::SshServer;
+use crate::model::transfer::TransferMode;
 use home::home_dir;
 use std::collections::HashMap;
 use std::fs;
@@ -23,41 +28,88 @@ pub fn read_config() -> Option<String> {
     return None;
 }
 
-pub fn parse_config(
-    config: String,
-) -> (
-    HashMap<String, SshServer>,
-    HashMap<String, Folder>,
-    HashMap<String, Link>,
-    Folder,
-) {
-    let config: TomlConfig = toml::from_str(config.as_str()).expect("Error parsing config");
+pub fn parse_config(config: String) -> Result<Config, String> {
+    let config: TomlConfig =
+        toml::from_str(config.as_str()).map_err(|err| format!("Error parsing config: {}", err))?;
+
+    let loopback_roots: HashMap<String, String> = config
+        .ssh
+        .iter()
+        .filter_map(|(key, toml_ssh_server)| {
+            let root = toml_ssh_server.loopback.clone()?;
+            Some((key.clone(), root))
+        })
+        .collect();
 
     let mut folders: HashMap<String, Folder> = HashMap::new();
     for toml_folder in config.folders {
+        let target = FolderType::new(&toml_folder.0, &toml_folder.1)?;
+        if let TomlType::Ssh = toml_folder.1.target {
+            let ssh_key = toml_folder
+                .1
+                .ssh_key
+                .as_ref()
+                .ok_or(format!("No ssh_key found for folder: {}", toml_folder.0))?;
+            if !config.ssh.contains_key(ssh_key) {
+                return Err(format!(
+                    "Ssh server not found for folder {}: {}",
+                    toml_folder.0, ssh_key
+                ));
+            }
+        }
+        // Folders of a loopback host are found under its root
+        let loopback_root = match toml_folder.1.target {
+            TomlType::Ssh => toml_folder
+                .1
+                .ssh_key
+                .as_ref()
+                .and_then(|ssh_key| loopback_roots.get(ssh_key)),
+            TomlType::Local | TomlType::S3 | TomlType::WebDav | TomlType::Archive => None,
+        };
+        let path = match loopback_root {
+            Some(root) => format!(
+                "{}/{}",
+                root.trim_end_matches('/'),
+                toml_folder.1.path.trim_start_matches('/')
+            ),
+            None => toml_folder.1.path,
+        };
         let folder = Folder {
             name: toml_folder.0,
-            path: toml_folder.1.path,
-            target: FolderType::get_folder_type(toml_folder.1.target),
+            path,
+            target,
             ssh_key: toml_folder.1.ssh_key,
+            hooks: Hooks::new(
+                toml_folder.1.pre_sync,
+                toml_folder.1.post_sync,
+                toml_folder.1.pre_sync_abort,
+            ),
         };
         folders.insert(folder.name.clone(), folder);
     }
-    let work_folder = folders
-        .get(&config.local_work_dir)
-        .expect("Local work folder required")
-        .clone();
+    // Work folders are only needed for staged transfers
+    let work_folder = match config.local_work_dir {
+        Some(local_work_dir) => Some(
+            folders
+                .get(&local_work_dir)
+                .ok_or(format!("Local work folder not found: {}", local_work_dir))?
+                .clone(),
+        ),
+        None => None,
+    };
 
     let mut ssh_servers: HashMap<String, SshServer> = HashMap::new();
     for toml_ssh_server in config.ssh {
-        let ssh_work_folder = folders
-            .get(&toml_ssh_server.1.work_dir)
-            .expect("Remote work folder required");
-        let ssh_server = SshServer::new(
-            toml_ssh_server.0,
-            toml_ssh_server.1,
-            ssh_work_folder.clone(),
-        );
+        let ssh_work_folder = match &toml_ssh_server.1.work_dir {
+            Some(work_dir) => Some(
+                folders
+                    .get(work_dir)
+                    .ok_or(format!("Remote work folder not found: {}", work_dir))?
+                    .clone(),
+            ),
+            None => None,
+        };
+        let ssh_server = SshServer::new(toml_ssh_server.0, toml_ssh_server.1, ssh_work_folder)?;
         ssh_servers.insert(ssh_server.key.clone(), ssh_server);
     }
 
@@ -66,19 +118,45 @@ pub fn parse_config(
         let local_folder = folders.get(&toml_link.1.local);
         let target_folder = folders.get(&toml_link.1.target);
 
-        if local_folder.is_some() && target_folder.is_some() {
+        if let (Some(local_folder), Some(target_folder)) = (local_folder, target_folder) {
+            let schedule = Schedule::new(&toml_link.0, toml_link.1.schedule);
             let link = Link {
                 name: toml_link.0,
-                local: local_folder.unwrap().clone(),
-                target: target_folder.unwrap().clone(),
+                local: local_folder.clone(),
+                target: target_folder.clone(),
                 paths: toml_link.1.paths,
                 partial_only: toml_link.1.partial_only.eq("true"),
+                compression: Compression::new(
+                    toml_link.1.compression,
+                    toml_link.1.compression_level,
+                ),
+                transfer: toml_link.1.transfer.map(TransferMode::get_transfer_mode),
+                checksum: toml_link
+                    .1
+                    .checksum
+                    .map(ChecksumAlgorithm::get_checksum_algorithm),
+                verify: toml_link.1.verify.unwrap_or(false),
+                low_priority: toml_link.1.low_priority,
+                jobs: toml_link.1.jobs,
+                bundle: toml_link.1.bundle.unwrap_or(false),
+                exclude: toml_link.1.exclude.unwrap_or_default(),
+                schedule,
+                hooks: Hooks::new(
+                    toml_link.1.pre_sync,
+                    toml_link.1.post_sync,
+                    toml_link.1.pre_sync_abort,
+                ),
             };
             links.insert(link.name.clone(), link);
         } else {
-            println!("Unable to parse link in configuration");
+            log::warn!("Unable to parse link in configuration");
             continue;
         }
     }
-    return (ssh_servers, folders, links, work_folder);
+    return Ok(Config {
+        ssh_servers,
+        folders,
+        links,
+        work_folder,
+    });
 }
@@ -1,13 +1,161 @@
 use crate::model::{
+    checksum::ChecksumAlgorithm,
+    compression::Compression,
+    event::{EventKind, PlanStep},
     folder::{Folder, FolderType},
+    hook::HookStage,
+    listing::Listing,
+    options::SyncOptions,
+    plan::PlannedSync,
+    report::SyncReport,
     ssh::SshServer,
+    transfer::TransferMode,
 };
-use crate::service::ssh::{add_ssh_cmd, scp_cmd};
-use crate::service::tar::{tar_directory, untar_directory};
-use std::process::{Command, Stdio};
+use crate::service::checksum;
+use crate::service::compression::resolve as resolve_compression;
+use crate::service::endpoint::{self, scp_args, Endpoint};
+use crate::service::history;
+use crate::service::hook;
+use crate::service::lock;
+use crate::service::manifest;
+use crate::service::output;
+use crate::service::progress::{self, Measure, Probe};
+use crate::service::prompt;
+use crate::service::runner;
+use crate::service::tar::{
+    fingerprint_args, session_name, split_path, stage_directory, stream_directory, tar_directory,
+    untar_directory,
+};
+use crate::service::throttle;
+use crate::service::transfer::resolve as resolve_transfer;
+use log::{debug, error, info, trace};
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
 use std::{collections::HashMap, io};
 
-fn build_path(folder: &Folder, relative_path: &Option<String>) -> String {
+// Steps of a pull with the files and bytes it brings in
+pub type PullSteps = (Vec<Step>, u64, u64);
+
+pub struct Step {
+    name: &'static str,
+    args: Vec<String>,
+    // Command reading the stdout of args on its stdin
+    pipe_to: Option<Vec<String>>,
+    // Command on the destination whose output must match that of args
+    compare: Option<Compare>,
+    failure_msg: &'static str,
+    progress: Option<StepProgress>,
+    // Best effort command run when the step fails
+    cleanup: Option<Vec<String>>,
+    // Kbit/s, paces the bytes pumped through pipe_to
+    bandwidth_limit: Option<u32>,
+    // Exit code meaning the destination already held what the step would send
+    unchanged_code: Option<i32>,
+}
+
+struct Compare {
+    args: Vec<String>,
+    // Prefixes stripped from paths in the output of each side
+    from_root: String,
+    to_root: String,
+    // Files tar left out, skipped on both sides
+    exclude: Vec<String>,
+}
+
+// Everything the step builders need about one resolved sync
+pub struct SyncContext<'a> {
+    pub from_folder: &'a Folder,
+    pub to_folder: &'a Folder,
+    pub from: Box<dyn Endpoint + 'a>,
+    pub to: Box<dyn Endpoint + 'a>,
+    pub ssh_servers: &'a HashMap<String, SshServer>,
+    pub work_folder: &'a Option<Folder>,
+    // Relative to the folders, empty when the whole folder is synced
+    pub relative_paths: Vec<String>,
+    pub from_path: String,
+    pub to_path: String,
+    // Archived relative to the bases, one entry unless the paths of a link are bundled
+    pub from_base: String,
+    pub to_base: String,
+    pub from_members: Vec<String>,
+    pub to_members: Vec<String>,
+    pub compression: Compression,
+    pub checksum: ChecksumAlgorithm,
+    pub fresh: bool,
+    pub bandwidth_limit: Option<u32>,
+    pub low_priority: bool,
+    pub exclude: Vec<String>,
+}
+
+impl Step {
+    pub fn new(name: &'static str, args: Vec<String>, failure_msg: &'static str) -> Self {
+        Self {
+            name,
+            args,
+            pipe_to: None,
+            compare: None,
+            failure_msg,
+            progress: None,
+            cleanup: None,
+            bandwidth_limit: None,
+            unchanged_code: None,
+        }
+    }
+
+    fn compare(
+        name: &'static str,
+        args: Vec<String>,
+        compare: Compare,
+        failure_msg: &'static str,
+    ) -> Self {
+        let mut step = Step::new(name, args, failure_msg);
+        step.compare = Some(compare);
+        step
+    }
+
+    pub fn with_progress(mut self, label: &'static str, probe: Probe, total: Total) -> Self {
+        self.progress = Some(StepProgress {
+            label,
+            probe,
+            total,
+        });
+        self
+    }
+
+    pub fn with_cleanup(mut self, cleanup: Vec<String>) -> Self {
+        self.cleanup = Some(cleanup);
+        self
+    }
+
+    pub fn with_unchanged_code(mut self, unchanged_code: i32) -> Self {
+        self.unchanged_code = Some(unchanged_code);
+        self
+    }
+
+    fn preview(&self) -> String {
+        match (&self.pipe_to, &self.compare) {
+            (Some(pipe_to), _) => format!("{} | {}", self.args.join(" "), pipe_to.join(" ")),
+            (_, Some(compare)) => format!("{} == {}", self.args.join(" "), compare.args.join(" ")),
+            _ => self.args.join(" "),
+        }
+    }
+}
+
+// How a step's progress is observed while it runs
+struct StepProgress {
+    label: &'static str,
+    probe: Probe,
+    total: Total,
+}
+
+pub enum Total {
+    Source,
+    Archive,
+}
+
+pub fn build_path(folder: &Folder, relative_path: &Option<String>) -> String {
     if let Some(relative_path) = relative_path {
         return format!("{}/{}", folder.path.clone(), relative_path);
     }
@@ -19,191 +167,1155 @@ pub fn ls(
     folder: &Folder,
     ssh_servers: &HashMap<String, SshServer>,
     relative_path: &Option<String>,
-) -> () {
+) -> Result<Listing, String> {
     let path = build_path(folder, relative_path);
-    println!("SSH - {:?} - {:?}", folder, path);
-
-    let mut cmd_args = add_ssh_cmd(folder, ssh_servers, &mut Vec::new());
-    cmd_args.push("ls".to_string());
-    cmd_args.push("-l".to_string());
-    cmd_args.push(path.clone());
-    let first_cmd = cmd_args.first().expect("First argument required");
-    let mut ls_cmd = Command::new(first_cmd);
-    for cmd_arg in &cmd_args[1..] {
-        ls_cmd.arg(cmd_arg);
-    }
-
-    let ls_output = ls_cmd
-        .stdout(Stdio::inherit())
-        .output()
-        .expect("Failed to LS");
+    info!("Ls: {}", path);
+    trace!("Folder: {:?}", folder);
+
+    let endpoint = endpoint::get(folder, ssh_servers)?;
+    let cmd_args = endpoint.list(&path)?;
+    let ls_output = runner::get()
+        .output(&cmd_args)
+        .map_err(|err| format!("Failed to LS: {}", err))?;
+    if !ls_output.status.success() {
+        return Err(format!("Failed to LS: {}", path));
+    }
     let ls_output = String::from_utf8(ls_output.stdout).expect("Error converting Stdout");
-    println!("{}", ls_output);
+    let ls_output = endpoint.listing(&ls_output, &path);
+    return Ok(Listing {
+        path,
+        lines: ls_output.lines().map(|line| line.to_string()).collect(),
+    });
 }
 
-pub fn sync(
+pub fn verify(
     from_folder: &Folder,
     to_folder: &Folder,
-    work_folder: &Folder,
     ssh_servers: &HashMap<String, SshServer>,
     relative_path: &Option<String>,
-    force: bool,
-) -> () {
-    // preview argument, to help build prompts
-    // add ssh connection checks
+    link_checksum: &Option<ChecksumAlgorithm>,
+    exclude: &[String],
+) -> Result<(), String> {
     let from_path = build_path(from_folder, relative_path);
     let to_path = build_path(to_folder, relative_path);
-    println!("Sync: {:?} - {:?}", from_path, to_path);
+    info!("Verify: {} against {}", to_path, from_path);
+    if let Some(storage) = storage(from_folder, to_folder) {
+        return Err(format!(
+            "Verify is not supported for {} folders",
+            storage.name()
+        ));
+    }
+    let checksum = checksum::resolve(link_checksum, from_folder, to_folder, ssh_servers);
+    return manifest::verify(
+        from_folder,
+        &from_path,
+        to_folder,
+        &to_path,
+        ssh_servers,
+        checksum,
+        exclude,
+    );
+}
 
-    let is_from_ssh = match from_folder.target {
-        FolderType::Ssh => true,
-        _ => false,
+// One path, or one bundle of paths, resolved and checked but not yet confirmed
+struct SyncPlan<'a> {
+    context: SyncContext<'a>,
+    steps: Vec<Step>,
+    archive_size_args: Option<Vec<String>>,
+    streamed: Arc<AtomicU64>,
+    files: u64,
+    bytes: u64,
+    // Held on the destination while the steps run
+    lock_path: String,
+}
+
+// Plans every path up front, asks once and runs the confirmed plans on `jobs` threads
+pub fn sync(
+    from_folder: &Folder,
+    to_folder: &Folder,
+    work_folder: &Option<Folder>,
+    ssh_servers: &HashMap<String, SshServer>,
+    relative_paths: &[Option<String>],
+    options: &SyncOptions,
+) -> Vec<Result<SyncReport, String>> {
+    let transfer = resolve_transfer(&options.transfer, from_folder, to_folder, ssh_servers);
+    let units = match units(
+        transfer,
+        relative_paths,
+        options,
+        from_folder,
+        to_folder,
+        ssh_servers,
+    ) {
+        Ok(units) => units,
+        Err(err) => return vec![Err(err)],
     };
-    let is_to_ssh = match to_folder.target {
-        FolderType::Ssh => true,
-        _ => false,
+
+    // Before planning, so what a build hook writes is what gets sized and archived. An aborting
+    // hook calls off every path.
+    let pre_sync = hook::run(
+        HookStage::PreSync,
+        from_folder,
+        to_folder,
+        ssh_servers,
+        relative_paths,
+        options,
+    );
+    let mut results: Vec<Option<Result<SyncReport, String>>> = Vec::new();
+    let mut durations: Vec<u64> = Vec::new();
+    let mut plans = Vec::new();
+    for unit in units.iter() {
+        durations.push(0);
+        if let Err(err) = &pre_sync {
+            results.push(Some(Err(err.clone())));
+            continue;
+        }
+        match plan(
+            from_folder,
+            to_folder,
+            work_folder,
+            ssh_servers,
+            unit,
+            options,
+        ) {
+            Ok(sync_plan) => {
+                plans.push((results.len(), sync_plan));
+                results.push(None);
+            }
+            Err(err) => results.push(Some(Err(err))),
+        }
+    }
+
+    if !plans.is_empty() {
+        info!("Ready for transfer, would you like to continue? The following commands will run");
+        for (_, sync_plan) in plans.iter() {
+            if plans.len() > 1 {
+                info!(
+                    "{} -> {}",
+                    sync_plan.context.from_path, sync_plan.context.to_path
+                );
+            }
+            for step in sync_plan.steps.iter() {
+                info!("- {}", step.preview());
+            }
+        }
+    }
+
+    if !plans.is_empty() && !options.force && !confirm() {
+        let reason = "Skipping this folder because the sync was not confirmed";
+        info!("{}", reason);
+        for (index, sync_plan) in plans.drain(..) {
+            output::emit(EventKind::Skipped {
+                from: sync_plan.context.from_path.clone(),
+                to: sync_plan.context.to_path.clone(),
+                reason: reason.to_string(),
+            });
+            results[index] = Some(Ok(SyncReport {
+                files: sync_plan.files,
+                bytes: sync_plan.bytes,
+                skipped: true,
+            }));
+        }
+    }
+    if !plans.is_empty() {
+        let jobs = options.jobs.unwrap_or(1).clamp(1, plans.len());
+        // Several bars redrawing at once would garble each other
+        let show_progress = jobs == 1 && progress::enabled();
+        debug!("Running {} syncs on {} jobs", plans.len(), jobs);
+        let queue = Mutex::new(plans.into_iter());
+        let finished = Mutex::new(Vec::new());
+        thread::scope(|scope| {
+            for _ in 0..jobs {
+                scope.spawn(|| loop {
+                    let next = queue.lock().expect("Queue lock poisoned").next();
+                    let Some((index, sync_plan)) = next else {
+                        break;
+                    };
+                    let (from_path, to_path) = (
+                        sync_plan.context.from_path.clone(),
+                        sync_plan.context.to_path.clone(),
+                    );
+                    let start = Instant::now();
+                    let result = execute(sync_plan, options, show_progress);
+                    let duration_ms = start.elapsed().as_millis() as u64;
+                    output::emit(EventKind::SyncFinish {
+                        from: from_path,
+                        to: to_path,
+                        success: result.is_ok(),
+                        error: result.as_ref().err().cloned(),
+                    });
+                    finished.lock().expect("Results lock poisoned").push((
+                        index,
+                        result,
+                        duration_ms,
+                    ));
+                });
+            }
+        });
+        for (index, result, duration_ms) in finished.into_inner().expect("Results lock poisoned") {
+            results[index] = Some(result);
+            durations[index] = duration_ms;
+        }
+    }
+
+    let mut results: Vec<Result<SyncReport, String>> = results
+        .into_iter()
+        .map(|result| result.expect("Every path has a result"))
+        .collect();
+    let mode = match transfer {
+        TransferMode::Staged => "staged",
+        TransferMode::Stream => "stream",
     };
-    if is_from_ssh && is_to_ssh {
-        println!("Only one folder can be remote");
-        return;
+    for ((unit, result), duration_ms) in units.iter().zip(results.iter()).zip(durations) {
+        let (from, to, relative_path, mode) = match unit.as_slice() {
+            [relative_path] => (
+                build_path(from_folder, relative_path),
+                build_path(to_folder, relative_path),
+                relative_path.clone(),
+                mode,
+            ),
+            _ => (
+                from_folder.path.clone(),
+                to_folder.path.clone(),
+                Some(unit.iter().flatten().cloned().collect::<Vec<_>>().join(" ")),
+                "bundle",
+            ),
+        };
+        history::record(
+            (from_folder, from),
+            (to_folder, to),
+            relative_path,
+            mode,
+            options,
+            result,
+            duration_ms,
+        );
+    }
+    if relative_paths.len() > 1 {
+        info!("Results:");
+        for (unit, result) in units.iter().zip(results.iter()) {
+            let path = match unit.as_slice() {
+                [relative_path] => build_path(to_folder, relative_path),
+                _ => format!("{} ({} bundled paths)", to_folder.path, unit.len()),
+            };
+            match result {
+                Ok(report) if report.skipped => info!("- skipped {}", path),
+                Ok(_) => info!("- ok      {}", path),
+                Err(err) => info!("- failed  {}: {}", path, err),
+            }
+        }
+    }
+    // Only after everything landed, a failed hook counts as one more failure
+    let synced = results
+        .iter()
+        .all(|result| matches!(result, Ok(report) if !report.skipped));
+    if synced && !results.is_empty() {
+        if let Err(err) = hook::run(
+            HookStage::PostSync,
+            from_folder,
+            to_folder,
+            ssh_servers,
+            relative_paths,
+            options,
+        ) {
+            results.push(Err(err));
+        }
     }
+    return results;
+}
 
-    let from_work_folder = if is_from_ssh {
-        let ssh_server =
-            crate::service::ssh::get(from_folder.ssh_key.clone().unwrap(), ssh_servers)
-                .expect("SSH Server not found");
-        &ssh_server.work_folder
-    } else {
-        work_folder
+// Checks and plans like `sync`, without asking or running anything
+pub fn plan_only(
+    from_folder: &Folder,
+    to_folder: &Folder,
+    work_folder: &Option<Folder>,
+    ssh_servers: &HashMap<String, SshServer>,
+    relative_paths: &[Option<String>],
+    options: &SyncOptions,
+) -> Vec<Result<PlannedSync, String>> {
+    let transfer = resolve_transfer(&options.transfer, from_folder, to_folder, ssh_servers);
+    let units = match units(
+        transfer,
+        relative_paths,
+        options,
+        from_folder,
+        to_folder,
+        ssh_servers,
+    ) {
+        Ok(units) => units,
+        Err(err) => return vec![Err(err)],
     };
+    return units
+        .iter()
+        .map(|unit| {
+            let sync_plan = plan(
+                from_folder,
+                to_folder,
+                work_folder,
+                ssh_servers,
+                unit,
+                options,
+            )?;
+            Ok(PlannedSync {
+                from: sync_plan.context.from_path.clone(),
+                to: sync_plan.context.to_path.clone(),
+                relative_paths: unit.iter().flatten().cloned().collect(),
+                steps: plan_steps(&sync_plan.steps),
+                files: sync_plan.files,
+                bytes: sync_plan.bytes,
+            })
+        })
+        .collect();
+}
 
-    let to_work_folder = if is_to_ssh {
-        let ssh_server = crate::service::ssh::get(to_folder.ssh_key.clone().unwrap(), ssh_servers);
-        let ssh_server = ssh_server.expect("SSH Server not found");
-        &ssh_server.work_folder
-    } else {
-        work_folder
+// A folder written whole always holds every path of the sync
+fn units(
+    transfer: TransferMode,
+    relative_paths: &[Option<String>],
+    options: &SyncOptions,
+    from_folder: &Folder,
+    to_folder: &Folder,
+    ssh_servers: &HashMap<String, SshServer>,
+) -> Result<Vec<Vec<Option<String>>>, String> {
+    let whole = endpoint::get(from_folder, ssh_servers)?.whole()
+        || endpoint::get(to_folder, ssh_servers)?.whole();
+    if relative_paths.len() > 1 && (whole || options.bundle && transfer == TransferMode::Staged) {
+        return Ok(vec![relative_paths.to_vec()]);
+    }
+    return Ok(relative_paths
+        .iter()
+        .map(|relative_path| vec![relative_path.clone()])
+        .collect());
+}
+
+fn plan_steps(steps: &[Step]) -> Vec<PlanStep> {
+    return steps
+        .iter()
+        .map(|step| PlanStep {
+            name: step.name.to_string(),
+            argv: step.args.clone(),
+            pipe_argv: step.pipe_to.clone(),
+            compare_argv: step.compare.as_ref().map(|compare| compare.args.clone()),
+        })
+        .collect();
+}
+
+// Declined when no prompt was set, a library caller passes force instead
+pub fn confirm() -> bool {
+    return prompt::get().is_some_and(|prompt| prompt.confirm());
+}
+
+fn plan<'a>(
+    from_folder: &'a Folder,
+    to_folder: &'a Folder,
+    work_folder: &'a Option<Folder>,
+    ssh_servers: &'a HashMap<String, SshServer>,
+    unit: &[Option<String>],
+    options: &SyncOptions,
+) -> Result<SyncPlan<'a>, String> {
+    // add ssh connection checks
+    let (relative_path, from_path, to_path, from_base, to_base, from_members, to_members) =
+        match unit {
+            [relative_path] => {
+                let from_path = build_path(from_folder, relative_path);
+                let to_path = build_path(to_folder, relative_path);
+                let (from_base, from_member) = split_path(from_path.clone());
+                let (to_base, to_member) = split_path(to_path.clone());
+                (
+                    relative_path.clone(),
+                    from_path,
+                    to_path,
+                    from_base,
+                    to_base,
+                    vec![from_member],
+                    vec![to_member],
+                )
+            }
+            _ => {
+                let members: Vec<String> = unit.iter().flatten().cloned().collect();
+                (
+                    None,
+                    from_folder.path.clone(),
+                    to_folder.path.clone(),
+                    from_folder.path.clone(),
+                    to_folder.path.clone(),
+                    members.clone(),
+                    members,
+                )
+            }
+        };
+    let source_paths: Vec<String> = from_members
+        .iter()
+        .map(|member| format!("{}/{}", from_base, member))
+        .collect();
+    info!("Sync: {} -> {}", from_path, to_path);
+    if from_members.len() > 1 {
+        info!("Bundling: {}", from_members.join(", "));
+    }
+    trace!("From folder: {:?}", from_folder);
+    trace!("To folder: {:?}", to_folder);
+
+    let (from, to) = (
+        endpoint::get(from_folder, ssh_servers)?,
+        endpoint::get(to_folder, ssh_servers)?,
+    );
+    let compression =
+        resolve_compression(&options.compression, from_folder, to_folder, ssh_servers);
+    let transfer = resolve_transfer(&options.transfer, from_folder, to_folder, ssh_servers);
+    let checksum = checksum::resolve(&options.checksum, from_folder, to_folder, ssh_servers);
+    let bandwidth_limit = throttle::resolve_bandwidth_limit(
+        &options.bandwidth_limit,
+        from_folder,
+        to_folder,
+        ssh_servers,
+    );
+    let low_priority =
+        throttle::resolve_low_priority(&options.low_priority, from_folder, to_folder, ssh_servers);
+    debug!(
+        "Compression: {:?}, transfer: {:?}, checksum: {:?}, bandwidth limit: {:?}, low priority: {}",
+        compression, transfer, checksum, bandwidth_limit, low_priority
+    );
+
+    let context = SyncContext {
+        from_folder,
+        to_folder,
+        from,
+        to,
+        ssh_servers,
+        work_folder,
+        relative_paths: trimmed_paths(unit),
+        from_path: from_path.clone(),
+        to_path: to_path.clone(),
+        from_base,
+        to_base,
+        from_members,
+        to_members,
+        compression,
+        checksum,
+        fresh: options.fresh,
+        bandwidth_limit,
+        low_priority,
+        exclude: options.exclude.clone(),
+    };
+    check_storage(&context, unit, options)?;
+    // Storage folders count what a pull brings themselves
+    let pulled = match context.from.pull(&context) {
+        Some(pulled) => Some(pulled?),
+        None => None,
+    };
+    let (files, bytes) = match &pulled {
+        Some((_, files, bytes)) => (*files, *bytes),
+        None => {
+            let tar_source_exists_args = context.from.stat(&source_paths)?;
+            let check_folder_output = runner::get()
+                .output(&tar_source_exists_args)
+                .map_err(|err| format!("Failed to Check Folder: {}", err))?;
+            if !check_folder_output.status.success() {
+                return Err("Error: From Folder Does Not Exist".to_string());
+            }
+
+            let mut count_files_args: Vec<String> = Vec::new();
+            count_files_args.push("find".to_string());
+            count_files_args.extend(source_paths.clone());
+            count_files_args.push("-type".to_string());
+            count_files_args.push("f".to_string());
+            let count_files_args = context.from.exec(count_files_args)?;
+            let count_files_output = runner::get()
+                .output(&count_files_args)
+                .map_err(|err| format!("Failed to Count Files: {}", err))?;
+            let files = String::from_utf8_lossy(&count_files_output.stdout)
+                .lines()
+                .count() as u64;
+            let source_size_args = context
+                .from
+                .exec(progress::probe_args(Measure::Tree, source_paths))?;
+            let bytes = progress::measure(&source_size_args, Measure::Tree).unwrap_or(0);
+            (files, bytes)
+        }
+    };
+    output::emit(EventKind::Files {
+        path: from_path.clone(),
+        count: files,
+        bytes,
+    });
+
+    let streamed = Arc::new(AtomicU64::new(0));
+    let (steps, archive_size_args) = match pulled {
+        Some((steps, _, _)) => (steps, None),
+        None => match (context.to.push(&context), transfer) {
+            (Some(steps), _) => (steps?, None),
+            (None, TransferMode::Staged) => staged_steps(&context, work_folder)?,
+            (None, TransferMode::Stream) => (stream_steps(&context, streamed.clone())?, None),
+        },
+    };
+    // Nothing runs for an unchanged source, so nothing is transferred
+    let (files, bytes) = match steps.is_empty() {
+        true => (0, 0),
+        false => (files, bytes),
+    };
+
+    output::emit(EventKind::Plan {
+        from: from_path.clone(),
+        to: to_path.clone(),
+        relative_path,
+        bundled: if context.from_members.len() > 1 {
+            context.from_members.clone()
+        } else {
+            Vec::new()
+        },
+        steps: plan_steps(&steps),
+    });
+    let lock_path = lock::lock_path(to_folder, &context.to_path, work_folder, ssh_servers)?;
+    return Ok(SyncPlan {
+        context,
+        steps,
+        archive_size_args,
+        streamed,
+        files,
+        bytes,
+        lock_path,
+    });
+}
+
+// Runs the steps of a plan while holding the destination lock
+fn execute(
+    sync_plan: SyncPlan,
+    options: &SyncOptions,
+    show_progress: bool,
+) -> Result<SyncReport, String> {
+    let (to_folder, ssh_servers) = (sync_plan.context.to_folder, sync_plan.context.ssh_servers);
+    let lock_path = sync_plan.lock_path.clone();
+    lock::acquire(
+        to_folder,
+        &lock_path,
+        ssh_servers,
+        options.wait.unwrap_or(false),
+    )?;
+    let result = run_plan(sync_plan, options, show_progress);
+    lock::release(to_folder, &lock_path, ssh_servers);
+    return result;
+}
+
+fn run_plan(
+    sync_plan: SyncPlan,
+    options: &SyncOptions,
+    show_progress: bool,
+) -> Result<SyncReport, String> {
+    let SyncPlan {
+        context,
+        steps,
+        archive_size_args,
+        streamed,
+        files,
+        bytes,
+        lock_path: _,
+    } = sync_plan;
+    let (from_folder, to_folder, ssh_servers, checksum) = (
+        context.from_folder,
+        context.to_folder,
+        context.ssh_servers,
+        context.checksum,
+    );
+    let member_paths: Vec<(String, String)> = context
+        .from_members
+        .iter()
+        .zip(context.to_members.iter())
+        .map(|(from_member, to_member)| {
+            (
+                format!("{}/{}", context.from_base, from_member),
+                format!("{}/{}", context.to_base, to_member),
+            )
+        })
+        .collect();
+
+    // Taken before archiving so the destination is checked against what was sent
+    let mut source_manifests = Vec::new();
+    if options.verify {
+        for (from_path, _) in member_paths.iter() {
+            source_manifests.push(manifest::exclude(
+                manifest::build(from_folder, from_path, ssh_servers, checksum)?,
+                &options.exclude,
+            ));
+        }
+    }
+
+    let mut unchanged = false;
+    for mut step in steps {
+        let tracker = match step.progress.take() {
+            Some(step_progress) if show_progress => {
+                let total = match step_progress.total {
+                    Total::Source => bytes,
+                    // Measured once the archive exists, it may be left from an earlier run
+                    Total::Archive => archive_size_args
+                        .as_ref()
+                        .and_then(|args| progress::measure(args, Measure::File))
+                        .unwrap_or(0),
+                };
+                Some(progress::start(
+                    step_progress.label,
+                    total,
+                    step_progress.probe,
+                ))
+            }
+            _ => None,
+        };
+        let result = run_step(&step, &streamed);
+        if let Some(tracker) = tracker {
+            tracker.finish();
+        }
+        if let (Err(_), Some(cleanup)) = (&result, &step.cleanup) {
+            debug!("Cleaning up after {}: {}", step.name, cleanup.join(" "));
+            let _ = runner::get().run(cleanup);
+        }
+        unchanged |= result?;
+    }
+
+    for ((from_path, to_path), source_manifest) in member_paths.iter().zip(source_manifests) {
+        let destination_manifest = manifest::build(to_folder, to_path, ssh_servers, checksum)?;
+        manifest::report(
+            from_path,
+            to_path,
+            &manifest::compare(&source_manifest, &destination_manifest),
+        )?;
+    }
+
+    // Nothing was sent, like a pull of an unchanged source
+    let (files, bytes) = match unchanged {
+        true => (0, 0),
+        false => (files, bytes),
     };
+    return Ok(SyncReport {
+        files,
+        bytes,
+        skipped: false,
+    });
+}
+
+// Archive into the source work folder, copy it across and extract from the destination work folder
+fn staged_steps(
+    context: &SyncContext,
+    work_folder: &Option<Folder>,
+) -> Result<(Vec<Step>, Option<Vec<String>>), String> {
+    let (from_folder, to_folder) = (context.from_folder, context.to_folder);
+    let compression = &context.compression;
+    let missing_work_folder = "A work folder is required for staged transfers".to_string();
+    let from_work_folder = context
+        .from
+        .work_folder(work_folder)
+        .as_ref()
+        .ok_or(missing_work_folder.clone())?;
+    let to_work_folder = context
+        .to
+        .work_folder(work_folder)
+        .as_ref()
+        .ok_or(missing_work_folder)?;
+    // Folders on the same machine share its work folder, the archive is already in place
+    let is_remote = context.from.host() != context.to.host();
 
-    let (tar_name, mut tar_source_exists_args, mut create_tar_args, mut delete_from_tar_args) =
-        tar_directory(from_path.clone(), from_work_folder);
-    let tar_source_exists_args = add_ssh_cmd(from_folder, ssh_servers, &mut tar_source_exists_args);
-    let create_tar_args = add_ssh_cmd(from_folder, ssh_servers, &mut create_tar_args);
-    let delete_from_tar_args = add_ssh_cmd(from_folder, ssh_servers, &mut delete_from_tar_args);
-    let mut copy_to_folder: Vec<String> = Vec::new();
+    let session = session_name(
+        from_folder,
+        &context.from_base,
+        to_folder,
+        &context.to_base,
+        &context.from_members,
+    );
+    let (tar_name, _, create_tar_args, seal_tar_args, delete_from_tar_args) = tar_directory(
+        context.from_base.clone(),
+        &context.from_members,
+        from_work_folder,
+        &session,
+        compression,
+        &context.exclude,
+    );
+    let create_tar_args = context.from.exec(prioritize(context, create_tar_args))?;
+    let seal_tar_args = context.from.exec(seal_tar_args)?;
 
     let (
-        mut verify_tar_args,
-        mut make_path_to_target_folder_args,
-        mut delete_target_folder_args,
-        mut untar_folder_args,
-        mut delete_to_tar_args,
-    ) = untar_directory(to_path.clone(), to_work_folder, tar_name.clone());
-    let verify_tar_args = add_ssh_cmd(to_folder, ssh_servers, &mut verify_tar_args);
-    let make_path_to_target_folder_args =
-        add_ssh_cmd(to_folder, ssh_servers, &mut make_path_to_target_folder_args);
-    let delete_target_folder_args =
-        add_ssh_cmd(to_folder, ssh_servers, &mut delete_target_folder_args);
-    let untar_folder_args = add_ssh_cmd(to_folder, ssh_servers, &mut untar_folder_args);
-    let delete_to_tar_args = add_ssh_cmd(to_folder, ssh_servers, &mut delete_to_tar_args);
-
-    if is_from_ssh || is_to_ssh {
-        let scp_cmd = scp_cmd(
-            from_folder,
-            to_folder,
-            tar_name.clone(),
-            from_work_folder,
-            to_work_folder,
-            ssh_servers,
+        verify_tar_args,
+        make_path_to_target_folder_args,
+        delete_target_folder_args,
+        untar_folder_args,
+        delete_to_tar_args,
+    ) = untar_directory(
+        context.to_base.clone(),
+        &context.to_members,
+        to_work_folder,
+        tar_name.clone(),
+        compression,
+    );
+    let verify_tar_args = context.to.exec(verify_tar_args)?;
+    let make_path_to_target_folder_args = context.to.exec(make_path_to_target_folder_args)?;
+    let delete_target_folder_args = context.to.exec(delete_target_folder_args)?;
+    let untar_folder_args = context.to.exec(prioritize(context, untar_folder_args))?;
+
+    let from_tar_path = format!("{}/{}", from_work_folder.path, tar_name);
+    let to_tar_path = format!("{}/{}", to_work_folder.path, tar_name);
+    // Fingerprint of the source the archive was made from, kept next to it
+    let source_record = format!("{}.source", from_tar_path);
+    let source_paths: Vec<String> = context
+        .from_members
+        .iter()
+        .map(|from_member| format!("{}/{}", context.from_base, from_member))
+        .collect();
+    let mut delete_from_tar_args = delete_from_tar_args;
+    let mut delete_to_tar_args = delete_to_tar_args;
+    match is_remote {
+        true => delete_from_tar_args.push(source_record.clone()),
+        false => delete_to_tar_args.push(source_record.clone()),
+    }
+    let delete_from_tar_args = context.from.exec(delete_from_tar_args)?;
+    let delete_to_tar_args = context.to.exec(delete_to_tar_args)?;
+    let from_part_size_args = context.from.exec(progress::probe_args(
+        Measure::File,
+        vec![format!("{}.part", from_tar_path)],
+    ))?;
+    let from_tar_size_args = context.from.exec(progress::probe_args(
+        Measure::File,
+        vec![from_tar_path.clone()],
+    ))?;
+    let to_tar_size_args = context.to.exec(progress::probe_args(
+        Measure::File,
+        vec![to_tar_path.clone()],
+    ))?;
+    let to_tree_size_args = context.to.exec(progress::probe_args(
+        Measure::Tree,
+        context
+            .to_members
+            .iter()
+            .map(|to_member| format!("{}/{}", context.to_base, to_member))
+            .collect(),
+    ))?;
+
+    // Archives left by an interrupted run of this session are picked up where they stopped,
+    // unless the source changed since
+    let archive_size = if context.fresh {
+        None
+    } else {
+        progress::measure(&from_tar_size_args, Measure::File)
+    };
+    let stale = archive_size.is_some() && {
+        let fingerprint = capture(
+            &context
+                .from
+                .exec(fingerprint_args(&source_paths, "/dev/stdout"))?,
+        );
+        let recorded = capture(
+            &context
+                .from
+                .exec(vec!["cat".to_string(), source_record.clone()])?,
+        );
+        fingerprint.is_none() || fingerprint != recorded
+    };
+    if stale {
+        info!(
+            "Rebuilding {}: the source changed since its archive was made",
+            session
+        );
+    }
+    let archive_size = archive_size.filter(|_| !stale);
+    let copied_size = match archive_size {
+        Some(_) if is_remote => progress::measure(&to_tar_size_args, Measure::File),
+        _ => None,
+    };
+    if let Some(archive_size) = archive_size {
+        info!(
+            "Resuming {}: reusing its {} byte archive, {} bytes already copied",
+            session,
+            archive_size,
+            copied_size.unwrap_or(0)
+        );
+    }
+
+    // A resumed copy is always hashed, whatever the checksum setting
+    let checksum_program = match copied_size {
+        Some(_) => context
+            .checksum
+            .program()
+            .or(ChecksumAlgorithm::Sha256.program()),
+        None => context.checksum.program(),
+    };
+    let delete_partial_args = context.to.remove(std::slice::from_ref(&to_tar_path))?;
+
+    // Without a copy both sides would hash the very same file
+    let verify_tar_step = match checksum_program {
+        Some(program) if is_remote => Step::compare(
+            "verify_tar",
+            context
+                .from
+                .exec(checksum::file_args(program, from_tar_path.clone()))?,
+            Compare {
+                args: context
+                    .to
+                    .exec(checksum::file_args(program, to_tar_path.clone()))?,
+                from_root: from_work_folder.path.clone(),
+                to_root: to_work_folder.path.clone(),
+                exclude: Vec::new(),
+            },
+            "Failed to Verify Tar Checksum",
+        )
+        // Otherwise the next run would resume onto a corrupt copy
+        .with_cleanup(delete_partial_args),
+        _ => Step::new("verify_tar", verify_tar_args, "Failed to Verify Tar"),
+    };
+
+    let mut steps = Vec::new();
+    // A copy of it is overwritten anyway
+    if stale {
+        steps.push(Step::new(
+            "delete_stale_tar",
+            context.from.remove(std::slice::from_ref(&from_tar_path))?,
+            "Failed to Delete Stale Tar",
+        ));
+    }
+    if archive_size.is_none() {
+        steps.push(Step::new(
+            "record_source",
+            context
+                .from
+                .exec(fingerprint_args(&source_paths, &source_record))?,
+            "Failed to Record Source",
+        ));
+        steps.push(
+            Step::new("create_tar", create_tar_args, "Failed to Create Tar").with_progress(
+                "Archive",
+                Probe::Command(from_part_size_args, Measure::File),
+                Total::Source,
+            ),
         );
-        for cmd in scp_cmd.iter() {
-            copy_to_folder.push(cmd.to_string());
+        steps.push(Step::new("seal_tar", seal_tar_args, "Failed to Seal Tar"));
+    }
+    match (archive_size, copied_size) {
+        _ if !is_remote => (),
+        (Some(archive_size), Some(copied_size)) if copied_size == archive_size => {
+            info!("Archive of {} was already copied", session);
         }
-    } else {
-        copy_to_folder.push("cp".to_string());
-        copy_to_folder.push("-R".to_string());
-        copy_to_folder.push(from_path.clone());
-        copy_to_folder.push(to_path.clone());
-    };
-
-    let check_folder_arg = tar_source_exists_args
-        .first()
-        .expect("First argument required");
-    let mut check_folder_cmd = Command::new(check_folder_arg);
-    for folder_arg in &tar_source_exists_args[1..] {
-        check_folder_cmd.arg(folder_arg);
-    }
-    let check_folder_output = check_folder_cmd
-        .stdout(Stdio::piped())
-        .output()
-        .expect("Failed to Check Folder");
-    let check_folder_output =
-        String::from_utf8(check_folder_output.stdout).expect("Error converting Stdout");
-    if check_folder_output.contains(&"No such".to_string()) {
-        println!("Error: From Folder Does Not Exist");
-        return;
-    }
-    println!("Ready for transfer, would you like to continue? The following commands will run");
-    println!("- {}", create_tar_args.join(" "));
-    println!("- {}", copy_to_folder.join(" "));
-    println!("- {}", make_path_to_target_folder_args.join(" "));
-    println!("- {}", verify_tar_args.join(" "));
-    println!("- {}", delete_target_folder_args.join(" "));
-    println!("- {}", untar_folder_args.join(" "));
-    println!("- {}", delete_to_tar_args.join(" "));
-    println!("- {}", delete_from_tar_args.join(" "));
-
-    if !force {
-        println!("Enter y to continue!");
-        let mut user_run_input = String::from("");
-        io::stdin()
-            .read_line(&mut user_run_input)
-            .expect("Failed to read line");
-        let user_run_input = user_run_input.trim().to_string();
-        if user_run_input != "y".to_string() {
-            println!("Skipping this folder because the user did not input 'y'");
-            return;
-        }
-    }
-
-    run_cmd(create_tar_args, true, "Failed to Create Tar".to_string());
-    run_cmd(copy_to_folder, true, "Failed to Copy Files".to_string());
-    run_cmd(
+        (Some(archive_size), Some(copied_size)) if copied_size < archive_size => {
+            let mut step = Step::new(
+                "resume_copy",
+                context.from.read_stream(&from_tar_path, copied_size)?,
+                "Failed to Resume Copy",
+            );
+            step.pipe_to = Some(context.to.write_stream(&to_tar_path, true)?);
+            step.bandwidth_limit = context.bandwidth_limit;
+            steps.push(step.with_progress(
+                "Transfer",
+                Probe::Command(to_tar_size_args, Measure::File),
+                Total::Archive,
+            ));
+        }
+        _ => {
+            let scp_args = scp_args(
+                (context.from.as_ref(), &from_tar_path),
+                (context.to.as_ref(), &to_tar_path),
+                context.bandwidth_limit,
+            );
+            // Between two remote hosts the archive passes through dsync
+            let step = match scp_args {
+                Some(scp_args) => Step::new("copy", scp_args, "Failed to Copy Files"),
+                None => {
+                    let mut step = Step::new(
+                        "copy",
+                        context.from.read_stream(&from_tar_path, 0)?,
+                        "Failed to Copy Files",
+                    );
+                    step.pipe_to = Some(context.to.write_stream(&to_tar_path, false)?);
+                    step.bandwidth_limit = context.bandwidth_limit;
+                    step
+                }
+            };
+            steps.push(step.with_progress(
+                "Transfer",
+                Probe::Command(to_tar_size_args, Measure::File),
+                Total::Archive,
+            ));
+        }
+    }
+    steps.push(Step::new(
+        "make_target_dirs",
         make_path_to_target_folder_args,
-        true,
-        "Make Target Directories".to_string(),
-    );
-    run_cmd(verify_tar_args, true, "Failed to Verify Tar".to_string());
-    run_cmd(
+        "Failed to Make Target Directories",
+    ));
+    steps.push(verify_tar_step);
+    steps.push(Step::new(
+        "delete_target",
         delete_target_folder_args,
-        true,
-        "Failed to Delete Target Folder".to_string(),
+        "Failed to Delete Target Folder",
+    ));
+    steps.push(
+        Step::new("untar", untar_folder_args, "Failed to Untar Archive").with_progress(
+            "Extract",
+            Probe::Command(to_tree_size_args, Measure::Tree),
+            Total::Source,
+        ),
     );
-    run_cmd(
+    steps.push(Step::new(
+        "delete_to_tar",
+        delete_to_tar_args,
+        "Failed to Delete To Tar",
+    ));
+    if is_remote {
+        steps.push(Step::new(
+            "delete_from_tar",
+            delete_from_tar_args,
+            "Failed to Delete From Tar",
+        ));
+    }
+    return Ok((steps, Some(from_tar_size_args)));
+}
+
+// Pipe tar on the source straight into tar on the destination through this process,
+// with a checksum the stream lands in a staging folder that is verified before the swap
+fn stream_steps(context: &SyncContext, streamed: Arc<AtomicU64>) -> Result<Vec<Step>, String> {
+    let checksum_program = context.checksum.program();
+    let staging = checksum_program.map(|_| stage_directory(context.to_path.clone()));
+    let staging_dir = staging
+        .as_ref()
+        .map(|(staging_dir, _, _, _)| staging_dir.clone());
+
+    let (
+        _,
+        create_tar_args,
+        make_path_to_target_folder_args,
+        delete_target_folder_args,
         untar_folder_args,
-        true,
-        "Failed to Untar Archive".to_string(),
+    ) = stream_directory(
+        context.from_path.clone(),
+        context.to_path.clone(),
+        &staging_dir,
+        &context.compression,
+        &context.exclude,
     );
-    run_cmd(
-        delete_to_tar_args,
-        true,
-        "Failed to Delete To Tar".to_string(),
+    let create_tar_args = context.from.exec(prioritize(context, create_tar_args))?;
+    let make_path_to_target_folder_args = context.to.exec(make_path_to_target_folder_args)?;
+    let delete_target_folder_args = context.to.exec(delete_target_folder_args)?;
+    let untar_folder_args = context.to.exec(prioritize(context, untar_folder_args))?;
+
+    let mut stream = Step::new("stream", create_tar_args, "Failed to Stream Archive")
+        .with_progress("Stream", Probe::Counter(streamed), Total::Source);
+    stream.pipe_to = Some(untar_folder_args);
+    stream.bandwidth_limit = context.bandwidth_limit;
+
+    let (Some(program), Some((staging_dir, make_staging_dir, move_into_place, delete_staging_dir))) =
+        (checksum_program, staging)
+    else {
+        return Ok(vec![
+            Step::new(
+                "make_target_dirs",
+                make_path_to_target_folder_args,
+                "Failed to Make Target Directories",
+            ),
+            Step::new(
+                "delete_target",
+                delete_target_folder_args,
+                "Failed to Delete Target Folder",
+            ),
+            stream,
+        ]);
+    };
+
+    let from_parent = context
+        .from_path
+        .rsplit_once('/')
+        .map(|(parent, _)| parent.to_string())
+        .unwrap_or_default();
+    let staged_path = format!(
+        "{}/{}",
+        staging_dir,
+        context.to_path.rsplit('/').next().unwrap_or_default()
+    );
+    let verify_files = Step::compare(
+        "verify_files",
+        context
+            .from
+            .exec(checksum::tree_args(program, context.from_path.clone()))?,
+        Compare {
+            args: context.to.exec(checksum::tree_args(program, staged_path))?,
+            from_root: from_parent,
+            to_root: staging_dir,
+            exclude: context.exclude.clone(),
+        },
+        "Failed to Verify File Checksums",
     );
-    run_cmd(
-        delete_from_tar_args,
-        true,
-        "Failed to Delete From Tar".to_string(),
+
+    return Ok(vec![
+        Step::new(
+            "make_staging_dir",
+            context.to.exec(make_staging_dir)?,
+            "Failed to Make Staging Directory",
+        ),
+        stream,
+        verify_files,
+        Step::new(
+            "delete_target",
+            delete_target_folder_args,
+            "Failed to Delete Target Folder",
+        ),
+        Step::new(
+            "move_into_place",
+            context.to.exec(move_into_place)?,
+            "Failed to Move Into Place",
+        ),
+        Step::new(
+            "delete_staging_dir",
+            context.to.exec(delete_staging_dir)?,
+            "Failed to Delete Staging Directory",
+        ),
+    ]);
+}
+
+// The type of whichever side is a storage service
+fn storage<'a>(from_folder: &'a Folder, to_folder: &'a Folder) -> Option<&'a FolderType> {
+    return [&from_folder.target, &to_folder.target]
+        .into_iter()
+        .find(|target| target.is_storage());
+}
+
+// Storage services run no commands, so they take whole paths from and to local folders
+fn check_storage(
+    context: &SyncContext,
+    unit: &[Option<String>],
+    options: &SyncOptions,
+) -> Result<(), String> {
+    let (from_folder, to_folder) = (context.from_folder, context.to_folder);
+    let Some(storage) = storage(from_folder, to_folder) else {
+        return Ok(());
+    };
+    let other = match from_folder.target.is_storage() {
+        true => &to_folder.target,
+        false => &from_folder.target,
+    };
+    if !matches!(other, FolderType::Local) {
+        return Err(format!(
+            "{} folders only sync with local folders",
+            storage.name()
+        ));
+    }
+    if unit.len() > 1 && !(context.from.whole() || context.to.whole()) {
+        return Err(format!(
+            "Bundles cannot be synced with {} folders",
+            storage.name()
+        ));
+    }
+    if options.verify {
+        return Err(format!(
+            "Verify is not supported for {} folders",
+            storage.name()
+        ));
+    }
+    return Ok(());
+}
+
+// Runs a single command as a step outside of a sync plan
+pub fn run(name: &'static str, args: Vec<String>, failure_msg: &'static str) -> Result<(), String> {
+    run_step(
+        &Step::new(name, args, failure_msg),
+        &Arc::new(AtomicU64::new(0)),
+    )?;
+    return Ok(());
+}
+
+// Pipes the stdout of args into pipe_to as a step outside of a sync plan
+pub fn pipe(
+    name: &'static str,
+    args: Vec<String>,
+    pipe_to: Vec<String>,
+    failure_msg: &'static str,
+) -> Result<(), String> {
+    let mut step = Step::new(name, args, failure_msg);
+    step.pipe_to = Some(pipe_to);
+    run_step(&step, &Arc::new(AtomicU64::new(0)))?;
+    return Ok(());
+}
+
+fn trimmed_paths(unit: &[Option<String>]) -> Vec<String> {
+    return unit
+        .iter()
+        .flatten()
+        .map(|relative_path| relative_path.trim_matches('/').to_string())
+        .collect();
+}
+
+// Stdout of a command that succeeded
+fn capture(cmd_args: &[String]) -> Option<String> {
+    let cmd_output = runner::get().output(cmd_args).ok()?;
+    if !cmd_output.status.success() {
+        return None;
+    }
+    return Some(String::from_utf8_lossy(&cmd_output.stdout).to_string());
+}
+
+pub fn prioritize(context: &SyncContext, cmd_args: Vec<String>) -> Vec<String> {
+    if context.low_priority {
+        return throttle::lower_priority(cmd_args);
+    }
+    return cmd_args;
+}
+
+// Ok(true) when the step exited with its unchanged code
+fn run_step(step: &Step, streamed: &Arc<AtomicU64>) -> Result<bool, String> {
+    output::emit(EventKind::StepStart {
+        name: step.name.to_string(),
+        argv: step.args.clone(),
+        pipe_argv: step.pipe_to.clone(),
+        compare_argv: step.compare.as_ref().map(|compare| compare.args.clone()),
+    });
+    debug!("Running {}: {}", step.name, step.preview());
+    let start = Instant::now();
+    let runner = runner::get();
+    let status = match (&step.pipe_to, &step.compare) {
+        (Some(pipe_to), _) => runner
+            .pipe(&step.args, pipe_to, streamed, step.bandwidth_limit)
+            .map(|status| (status.code(), status.success())),
+        (_, Some(compare)) => run_compare(&step.args, compare),
+        _ => runner
+            .run(&step.args)
+            .map(|status| (status.code(), status.success())),
+    };
+    let (exit_code, success) = status.map_err(|err| format!("{}: {}", step.failure_msg, err))?;
+    let unchanged = exit_code.is_some() && exit_code == step.unchanged_code;
+    let success = success || unchanged;
+    debug!(
+        "Finished {} in {}ms, success: {}",
+        step.name,
+        start.elapsed().as_millis(),
+        success
     );
+    output::emit(EventKind::StepFinish {
+        name: step.name.to_string(),
+        exit_code,
+        success,
+        duration_ms: start.elapsed().as_millis(),
+    });
+    if !success {
+        return Err(step.failure_msg.to_string());
+    }
+    return Ok(unchanged);
 }
 
-fn run_cmd(cmd_args: Vec<String>, print: bool, failure_msg: String) -> () {
-    let first_arg = cmd_args.first().expect("First argument required");
-    let mut cmd = Command::new(first_arg);
-    for folder_arg in &cmd_args[1..] {
-        cmd.arg(folder_arg);
+// Exit code is that of the first failing side, a mismatch alone reports none
+fn run_compare(cmd_args: &[String], compare: &Compare) -> io::Result<(Option<i32>, bool)> {
+    let mut sides = Vec::new();
+    for side_args in [cmd_args, &compare.args] {
+        let side_output = runner::get().output(side_args)?;
+        if !side_output.status.success() {
+            for line in String::from_utf8_lossy(&side_output.stderr).lines() {
+                error!("{}", line);
+            }
+            return Ok((side_output.status.code(), false));
+        }
+        sides.push(String::from_utf8_lossy(&side_output.stdout).to_string());
+    }
+
+    let from_lines = checksum::normalize(&sides[0], &compare.from_root, &compare.exclude);
+    let to_lines = checksum::normalize(&sides[1], &compare.to_root, &compare.exclude);
+    for line in from_lines.iter() {
+        trace!("Source checksum: {}", line);
     }
-    if print {
-        cmd.stdout(Stdio::inherit());
+    if from_lines != to_lines {
+        for line in from_lines.iter().filter(|line| !to_lines.contains(line)) {
+            error!("Checksum mismatch: {}", line);
+        }
+        return Ok((None, false));
     }
-    cmd.output().expect(&failure_msg);
+    return Ok((Some(0), true));
 }
@@ -0,0 +1,266 @@
+use crate::model::config::Config;
+use crate::model::event::EventKind;
+use crate::model::link::Link;
+use crate::model::options::SyncOptions;
+use crate::model::report::SyncReport;
+use crate::model::schedule::{LinkStatus, Schedule, ScheduleTime};
+use crate::service::output;
+use crate::service::syncer::Syncer;
+use chrono::{DateTime, Duration as ChronoDuration, Local, SecondsFormat, Utc};
+use home::home_dir;
+use log::{debug, info, warn};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+pub fn socket_path() -> PathBuf {
+    let home_dir = home_dir().expect("No Home Dir");
+    return Path::new(&home_dir.as_os_str()).join(".dirsync_daemon.sock");
+}
+
+// Runs every scheduled link when it is due, one at a time, until killed
+pub fn run(config: &Config, cli_options: SyncOptions) -> Result<(), String> {
+    let mut scheduled: Vec<&Link> = config
+        .links
+        .values()
+        .filter(|link| link.schedule.is_some())
+        .collect();
+    if scheduled.is_empty() {
+        return Err("No link has a schedule".to_string());
+    }
+    scheduled.sort_by(|left, right| left.name.cmp(&right.name));
+
+    let listener = bind()?;
+    let now = Local::now();
+    let mut next_runs: Vec<DateTime<Local>> = scheduled
+        .iter()
+        .map(|link| next_run(schedule_of(link), now, None))
+        .collect();
+    let statuses: Vec<LinkStatus> = scheduled
+        .iter()
+        .zip(next_runs.iter())
+        .map(|(link, next)| LinkStatus {
+            link: link.name.clone(),
+            direction: schedule_of(link).direction.name().to_string(),
+            schedule: schedule_of(link).text.clone(),
+            running: false,
+            next_run: Some(timestamp(next)),
+            last_run: None,
+            last_result: None,
+            last_error: None,
+        })
+        .collect();
+    let statuses = Arc::new(Mutex::new(statuses));
+    let served = statuses.clone();
+    thread::spawn(move || serve(listener, served));
+    for (link, next) in scheduled.iter().zip(next_runs.iter()) {
+        info!(
+            "{} {} {}, next run {}",
+            link.name,
+            schedule_of(link).direction.name(),
+            schedule_of(link).text,
+            timestamp(next)
+        );
+    }
+
+    loop {
+        let (index, due) = next_runs
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, due)| **due)
+            .map(|(index, due)| (index, *due))
+            .expect("At least one scheduled link");
+        let now = Local::now();
+        if due > now {
+            // Capped so a changed clock is noticed
+            let wait = (due - now).to_std().unwrap_or_default();
+            thread::sleep(wait.min(Duration::from_secs(60)));
+            continue;
+        }
+
+        let link = &scheduled[index];
+        let started = Local::now();
+        update(&statuses, index, |status| status.running = true);
+        let results = run_link(config, link, &cli_options);
+        let (result, error) = summarize(&results);
+        if let Some(error) = &error {
+            output::error(&format!(
+                "Scheduled {} of {} failed: {}",
+                schedule_of(link).direction.name(),
+                link.name,
+                error
+            ));
+        }
+        next_runs[index] = next_run(schedule_of(link), Local::now(), Some(started));
+        let next = timestamp(&next_runs[index]);
+        info!("{} finished {}, next run {}", link.name, result, next);
+        update(&statuses, index, |status| {
+            status.running = false;
+            status.last_run = Some(timestamp(&started));
+            status.last_result = Some(result.to_string());
+            status.last_error = error.clone();
+            status.next_run = Some(next.clone());
+        });
+    }
+}
+
+// Prints what a running daemon reports about its links
+pub fn status() -> Result<(), String> {
+    let path = socket_path();
+    let stream = UnixStream::connect(&path)
+        .map_err(|err| format!("No daemon is listening on {}: {}", path.display(), err))?;
+    let mut line = String::new();
+    BufReader::new(stream)
+        .read_line(&mut line)
+        .map_err(|err| format!("Failed to Read Daemon Status: {}", err))?;
+    let statuses: Vec<LinkStatus> = serde_json::from_str(&line)
+        .map_err(|err| format!("Failed to Read Daemon Status: {}", err))?;
+    for status in statuses {
+        if output::is_json() {
+            output::emit(EventKind::DaemonStatus(status));
+            continue;
+        }
+        let last = match (status.last_run, status.last_result) {
+            (Some(last_run), Some(last_result)) => format!("{} {}", last_result, last_run),
+            _ => "never".to_string(),
+        };
+        println!(
+            "{} {} {}: {}, last {}, next {}{}",
+            status.link,
+            status.direction,
+            status.schedule,
+            if status.running { "running" } else { "idle" },
+            last,
+            status.next_run.unwrap_or_default(),
+            status
+                .last_error
+                .map(|err| format!(" ({})", err))
+                .unwrap_or_default()
+        );
+    }
+    return Ok(());
+}
+
+fn schedule_of(link: &Link) -> &Schedule {
+    return link
+        .schedule
+        .as_ref()
+        .expect("Only scheduled links are run");
+}
+
+fn timestamp(time: &DateTime<Local>) -> String {
+    return time.to_rfc3339_opts(SecondsFormat::Secs, false);
+}
+
+fn next_run(
+    schedule: &Schedule,
+    now: DateTime<Local>,
+    last_start: Option<DateTime<Local>>,
+) -> DateTime<Local> {
+    match schedule.time {
+        ScheduleTime::Every(every) => {
+            let every = ChronoDuration::from_std(every).unwrap_or(ChronoDuration::MAX);
+            // A run longer than the interval is followed by the next one straight away, one
+            // too far off to tell when is never due
+            let next = last_start
+                .unwrap_or(now)
+                .checked_add_signed(every)
+                .unwrap_or(DateTime::<Utc>::MAX_UTC.with_timezone(&Local));
+            return next.max(now);
+        }
+        ScheduleTime::Daily(at) => {
+            let mut day = now.date_naive();
+            loop {
+                let next = day.and_time(at).and_local_timezone(Local).earliest();
+                if let Some(next) = next.filter(|next| *next > now) {
+                    return next;
+                }
+                day = day.succ_opt().expect("Date out of range");
+            }
+        }
+    }
+}
+
+fn run_link(
+    config: &Config,
+    link: &Link,
+    cli_options: &SyncOptions,
+) -> Vec<Result<SyncReport, String>> {
+    let direction = schedule_of(link).direction;
+    if link.partial_only && link.paths.is_empty() {
+        return vec![Err(format!(
+            "{} is partial only and has no paths to run on a schedule",
+            link.name
+        ))];
+    }
+    // Nobody is there to answer a prompt, or to retry a locked destination
+    let options = SyncOptions {
+        force: true,
+        direction: Some(direction),
+        wait: cli_options.wait.or(Some(true)),
+        ..cli_options.clone()
+    };
+    info!("Scheduled {} of {}", direction.name(), link.name);
+    return Syncer::new(config)
+        .options(options)
+        .run_link(&link.name, direction, &None);
+}
+
+fn summarize(results: &[Result<SyncReport, String>]) -> (&'static str, Option<String>) {
+    if let Some(Err(err)) = results.iter().find(|result| result.is_err()) {
+        return ("failed", Some(err.clone()));
+    }
+    if !results.is_empty() && results.iter().flatten().all(|report| report.skipped) {
+        return ("skipped", None);
+    }
+    return ("ok", None);
+}
+
+fn update(statuses: &Mutex<Vec<LinkStatus>>, index: usize, change: impl FnOnce(&mut LinkStatus)) {
+    let mut statuses = statuses
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    change(&mut statuses[index]);
+}
+
+// A socket left by a daemon that died is replaced, a live one is not
+fn bind() -> Result<UnixListener, String> {
+    let path = socket_path();
+    if path.exists() {
+        if UnixStream::connect(&path).is_ok() {
+            return Err(format!(
+                "A daemon is already listening on {}",
+                path.display()
+            ));
+        }
+        debug!("Removing stale socket {}", path.display());
+        let _ = fs::remove_file(&path);
+    }
+    return UnixListener::bind(&path)
+        .map_err(|err| format!("Failed to Listen on {}: {}", path.display(), err));
+}
+
+fn serve(listener: UnixListener, statuses: Arc<Mutex<Vec<LinkStatus>>>) -> () {
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                warn!("Status connection failed: {}", err);
+                continue;
+            }
+        };
+        let line = {
+            let statuses = statuses
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            serde_json::to_string(&*statuses).expect("Error serializing status")
+        };
+        if let Err(err) = writeln!(stream, "{}", line) {
+            debug!("Status client went away: {}", err);
+        }
+    }
+}
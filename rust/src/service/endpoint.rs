@@ -0,0 +1,476 @@
+use crate::model::archive::ArchiveFormat;
+use crate::model::folder::{Folder, FolderType};
+use crate::model::s3::S3Bucket;
+use crate::model::ssh::SshServer;
+use crate::model::webdav::WebDavServer;
+use crate::service::archive;
+use crate::service::core::{PullSteps, Step, SyncContext};
+use crate::service::s3;
+use crate::service::webdav;
+use std::collections::HashMap;
+
+// Where a folder lives, spelled as the commands that act on it. Every method returns the
+// arguments of a command for the step runner, so a sync only composes two endpoints.
+pub trait Endpoint: Send + Sync {
+    // Wraps a command so it runs wherever the folder lives, an error where no shell runs it
+    fn exec(&self, cmd_args: Vec<String>) -> Result<Vec<String>, String>;
+
+    // Machine the commands run on, None for this one
+    fn host(&self) -> Option<String>;
+
+    // Staging folder for archives, the local one is passed in for local endpoints
+    fn work_folder<'a>(&'a self, local_work_folder: &'a Option<Folder>) -> &'a Option<Folder>;
+
+    // How scp names a path here and the port it needs, None when scp cannot reach it
+    fn scp_path(&self, path: &str) -> Option<(String, Option<u32>)>;
+
+    // Command copying "{from}" to "{to}" in place of scp, for hosts with a transport
+    fn copy_command(&self) -> Option<Vec<String>> {
+        return None;
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<String>, String> {
+        return self.exec(vec!["ls".to_string(), "-l".to_string(), path.to_string()]);
+    }
+
+    // The output of list as lines to print
+    fn listing(&self, list_output: &str, _path: &str) -> String {
+        return list_output.to_string();
+    }
+
+    // Whether every path of a sync is written in one go
+    fn whole(&self) -> bool {
+        return false;
+    }
+
+    // Steps syncing into the folder, None when it is archived and copied like any other
+    fn push(&self, _context: &SyncContext) -> Option<Result<Vec<Step>, String>> {
+        return None;
+    }
+
+    // Steps syncing out of the folder and the files and bytes they bring
+    fn pull(&self, _context: &SyncContext) -> Option<Result<PullSteps, String>> {
+        return None;
+    }
+
+    // Size, mode and mtime of each path, fails when one is missing
+    fn stat(&self, paths: &[String]) -> Result<Vec<String>, String> {
+        let mut stat_args: Vec<String> = Vec::new();
+        stat_args.push("stat".to_string());
+        stat_args.push("-c".to_string());
+        stat_args.push("%s %a %Y %n".to_string());
+        stat_args.extend(paths.iter().cloned());
+        return self.exec(stat_args);
+    }
+
+    // Writes the file to stdout from byte `offset` on
+    fn read_stream(&self, path: &str, offset: u64) -> Result<Vec<String>, String> {
+        if offset == 0 {
+            return self.exec(vec!["cat".to_string(), path.to_string()]);
+        }
+        return self.exec(vec![
+            "tail".to_string(),
+            "-c".to_string(),
+            format!("+{}", offset + 1),
+            path.to_string(),
+        ]);
+    }
+
+    // Writes stdin to the file, after what it already holds when appending
+    fn write_stream(&self, path: &str, append: bool) -> Result<Vec<String>, String> {
+        let mut write_args: Vec<String> = Vec::new();
+        write_args.push("dd".to_string());
+        write_args.push(format!("of={}", path));
+        if append {
+            write_args.push("oflag=append".to_string());
+            write_args.push("conv=notrunc".to_string());
+        }
+        write_args.push("status=none".to_string());
+        return self.exec(write_args);
+    }
+
+    fn mkdir(&self, paths: &[String]) -> Result<Vec<String>, String> {
+        let mut mkdir_args: Vec<String> = Vec::new();
+        mkdir_args.push("mkdir".to_string());
+        mkdir_args.push("-p".to_string());
+        mkdir_args.extend(paths.iter().cloned());
+        return self.exec(mkdir_args);
+    }
+
+    fn remove(&self, paths: &[String]) -> Result<Vec<String>, String> {
+        let mut remove_args: Vec<String> = Vec::new();
+        remove_args.push("rm".to_string());
+        remove_args.push("-rf".to_string());
+        remove_args.extend(paths.iter().cloned());
+        return self.exec(remove_args);
+    }
+
+    fn rename(&self, from_path: &str, to_path: &str) -> Result<Vec<String>, String> {
+        return self.exec(vec![
+            "mv".to_string(),
+            from_path.to_string(),
+            to_path.to_string(),
+        ]);
+    }
+}
+
+pub struct LocalEndpoint;
+
+impl Endpoint for LocalEndpoint {
+    fn exec(&self, cmd_args: Vec<String>) -> Result<Vec<String>, String> {
+        return Ok(cmd_args);
+    }
+
+    fn host(&self) -> Option<String> {
+        return None;
+    }
+
+    fn work_folder<'a>(&'a self, local_work_folder: &'a Option<Folder>) -> &'a Option<Folder> {
+        return local_work_folder;
+    }
+
+    fn scp_path(&self, path: &str) -> Option<(String, Option<u32>)> {
+        return Some((path.to_string(), None));
+    }
+}
+
+pub struct SshEndpoint<'a> {
+    pub ssh_server: &'a SshServer,
+}
+
+impl Endpoint for SshEndpoint<'_> {
+    fn exec(&self, cmd_args: Vec<String>) -> Result<Vec<String>, String> {
+        if let Some(transport) = &self.ssh_server.transport {
+            let command = cmd_args
+                .iter()
+                .map(|cmd| shell_quote(cmd))
+                .collect::<Vec<String>>()
+                .join(" ");
+            let mut exec_args = self.fill(&transport.exec);
+            match exec_args.iter().any(|arg| arg.contains("{command}")) {
+                true => {
+                    for arg in exec_args.iter_mut() {
+                        *arg = arg.replace("{command}", &command);
+                    }
+                }
+                false => exec_args.push(command),
+            }
+            return Ok(exec_args);
+        }
+        let mut ssh_args: Vec<String> = Vec::new();
+        ssh_args.push("ssh".to_string());
+        ssh_args.push("-p".to_string());
+        ssh_args.push(self.ssh_server.port.to_string());
+        ssh_args.push(self.connection());
+        // ssh hands the command to the remote shell as one string
+        for cmd in cmd_args.iter() {
+            ssh_args.push(shell_quote(cmd));
+        }
+        return Ok(ssh_args);
+    }
+
+    fn host(&self) -> Option<String> {
+        return Some(format!("{}:{}", self.connection(), self.ssh_server.port));
+    }
+
+    fn work_folder<'a>(&'a self, _: &'a Option<Folder>) -> &'a Option<Folder> {
+        return &self.ssh_server.work_folder;
+    }
+
+    fn scp_path(&self, path: &str) -> Option<(String, Option<u32>)> {
+        let remote_path = match &self.ssh_server.transport {
+            // Without a copy command the files are streamed through exec
+            Some(transport) => {
+                transport.copy.as_ref()?;
+                self.fill(std::slice::from_ref(&transport.remote_path))[0].replace("{path}", path)
+            }
+            None => format!("{}:{}", self.connection(), path),
+        };
+        return Some((remote_path, Some(self.ssh_server.port)));
+    }
+
+    fn copy_command(&self) -> Option<Vec<String>> {
+        let transport = self.ssh_server.transport.as_ref()?;
+        return Some(self.fill(transport.copy.as_ref()?));
+    }
+}
+
+impl SshEndpoint<'_> {
+    fn connection(&self) -> String {
+        return format!("{}@{}", self.ssh_server.username, self.ssh_server.host);
+    }
+
+    // The transport template with this server's host, user and port in place
+    fn fill(&self, template: &[String]) -> Vec<String> {
+        return template
+            .iter()
+            .map(|arg| {
+                arg.replace("{host}", &self.ssh_server.host)
+                    .replace("{user}", &self.ssh_server.username)
+                    .replace("{port}", &self.ssh_server.port.to_string())
+            })
+            .collect();
+    }
+}
+
+// Stands in for a ssh host on this machine, commands get the same quoting ssh would give them
+pub struct LoopbackEndpoint<'a> {
+    pub ssh_server: &'a SshServer,
+}
+
+impl Endpoint for LoopbackEndpoint<'_> {
+    fn exec(&self, cmd_args: Vec<String>) -> Result<Vec<String>, String> {
+        let mut sh_args: Vec<String> = Vec::new();
+        sh_args.push("sh".to_string());
+        sh_args.push("-c".to_string());
+        sh_args.push(
+            cmd_args
+                .iter()
+                .map(|cmd| shell_quote(cmd))
+                .collect::<Vec<String>>()
+                .join(" "),
+        );
+        return Ok(sh_args);
+    }
+
+    fn host(&self) -> Option<String> {
+        return Some(format!("loopback:{}", self.ssh_server.key));
+    }
+
+    fn work_folder<'a>(&'a self, _: &'a Option<Folder>) -> &'a Option<Folder> {
+        return &self.ssh_server.work_folder;
+    }
+
+    // Copies pass through dsync like those between two hosts
+    fn scp_path(&self, _: &str) -> Option<(String, Option<u32>)> {
+        return None;
+    }
+}
+
+// Objects are listed and copied with the aws cli, commands on the folder itself fail
+pub struct S3Endpoint {
+    pub bucket: S3Bucket,
+}
+
+impl Endpoint for S3Endpoint {
+    fn exec(&self, _: Vec<String>) -> Result<Vec<String>, String> {
+        return Err("Commands cannot run in s3 folders".to_string());
+    }
+
+    fn host(&self) -> Option<String> {
+        return None;
+    }
+
+    fn work_folder<'a>(&'a self, local_work_folder: &'a Option<Folder>) -> &'a Option<Folder> {
+        return local_work_folder;
+    }
+
+    fn scp_path(&self, _: &str) -> Option<(String, Option<u32>)> {
+        return None;
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<String>, String> {
+        return Ok(s3::list_args(&self.bucket, path));
+    }
+
+    fn push(&self, context: &SyncContext) -> Option<Result<Vec<Step>, String>> {
+        return Some(s3::push_steps(&self.bucket, context));
+    }
+
+    fn pull(&self, context: &SyncContext) -> Option<Result<PullSteps, String>> {
+        return Some(s3::pull_steps(&self.bucket, context));
+    }
+}
+
+// Files are listed and copied with curl, commands on the folder itself fail
+pub struct WebDavEndpoint {
+    pub server: WebDavServer,
+}
+
+impl Endpoint for WebDavEndpoint {
+    fn exec(&self, _: Vec<String>) -> Result<Vec<String>, String> {
+        return Err("Commands cannot run in webdav folders".to_string());
+    }
+
+    fn host(&self) -> Option<String> {
+        return None;
+    }
+
+    fn work_folder<'a>(&'a self, local_work_folder: &'a Option<Folder>) -> &'a Option<Folder> {
+        return local_work_folder;
+    }
+
+    fn scp_path(&self, _: &str) -> Option<(String, Option<u32>)> {
+        return None;
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<String>, String> {
+        return Ok(webdav::propfind_args(&self.server, path));
+    }
+
+    fn listing(&self, list_output: &str, path: &str) -> String {
+        return webdav::listing(list_output, path, &self.server);
+    }
+
+    fn push(&self, context: &SyncContext) -> Option<Result<Vec<Step>, String>> {
+        return Some(webdav::push_steps(&self.server, context));
+    }
+
+    fn pull(&self, context: &SyncContext) -> Option<Result<PullSteps, String>> {
+        return Some(webdav::pull_steps(&self.server, context));
+    }
+}
+
+// A tar or zip file on this machine, written and read whole by tar or zip
+pub struct ArchiveEndpoint {
+    pub path: String,
+    pub format: ArchiveFormat,
+}
+
+impl Endpoint for ArchiveEndpoint {
+    fn exec(&self, _: Vec<String>) -> Result<Vec<String>, String> {
+        return Err("Commands cannot run in archive folders".to_string());
+    }
+
+    fn host(&self) -> Option<String> {
+        return None;
+    }
+
+    fn work_folder<'a>(&'a self, local_work_folder: &'a Option<Folder>) -> &'a Option<Folder> {
+        return local_work_folder;
+    }
+
+    fn scp_path(&self, _: &str) -> Option<(String, Option<u32>)> {
+        return None;
+    }
+
+    // The archive is listed whole and its entries under the path picked out
+    fn list(&self, _path: &str) -> Result<Vec<String>, String> {
+        return Ok(archive::entries_args(self.format, &self.path));
+    }
+
+    fn listing(&self, list_output: &str, path: &str) -> String {
+        let member = path.strip_prefix(&format!("{}/", self.path));
+        return archive::listing(list_output, member);
+    }
+
+    fn whole(&self) -> bool {
+        return true;
+    }
+
+    fn push(&self, context: &SyncContext) -> Option<Result<Vec<Step>, String>> {
+        return Some(archive::push_steps(self.format, context));
+    }
+
+    fn pull(&self, context: &SyncContext) -> Option<Result<PullSteps, String>> {
+        return Some(archive::pull_steps(self.format, context));
+    }
+}
+
+pub fn get<'a>(
+    folder: &Folder,
+    ssh_servers: &'a HashMap<String, SshServer>,
+) -> Result<Box<dyn Endpoint + 'a>, String> {
+    let endpoint: Box<dyn Endpoint + 'a> = match &folder.target {
+        FolderType::Local => Box::new(LocalEndpoint),
+        FolderType::Ssh => {
+            let ssh_key = folder
+                .ssh_key
+                .clone()
+                .ok_or(format!("No SSH Key Found for folder {}", folder.name))?;
+            let ssh_server = crate::service::ssh::get(ssh_key.clone(), ssh_servers)
+                .ok_or(format!("No SSH Server Found for ssh_key {}", ssh_key))?;
+            match ssh_server.loopback {
+                Some(_) => Box::new(LoopbackEndpoint { ssh_server }),
+                None => Box::new(SshEndpoint { ssh_server }),
+            }
+        }
+        FolderType::S3(bucket) => Box::new(S3Endpoint {
+            bucket: bucket.clone(),
+        }),
+        FolderType::WebDav(server) => Box::new(WebDavEndpoint {
+            server: server.clone(),
+        }),
+        FolderType::Archive(format) => Box::new(ArchiveEndpoint {
+            path: folder.path.clone(),
+            format: *format,
+        }),
+    };
+    return Ok(endpoint);
+}
+
+// Runs the commands kept next to a folder, such as its lock and hooks, on this machine for
+// storage folders
+pub fn shell<'a>(
+    folder: &Folder,
+    ssh_servers: &'a HashMap<String, SshServer>,
+) -> Result<Box<dyn Endpoint + 'a>, String> {
+    if folder.target.is_storage() {
+        return Ok(Box::new(LocalEndpoint));
+    }
+    return get(folder, ssh_servers);
+}
+
+// A scp between the endpoints, None when it cannot do the copy and the bytes have to be
+// piped through dsync instead
+pub fn scp_args(
+    (from, from_path): (&dyn Endpoint, &str),
+    (to, to_path): (&dyn Endpoint, &str),
+    bandwidth_limit: Option<u32>,
+) -> Option<Vec<String>> {
+    let (from_path, from_port) = from.scp_path(from_path)?;
+    let (to_path, to_port) = to.scp_path(to_path)?;
+    // scp takes a single port for both ends
+    let port = match (from_port, to_port) {
+        (Some(_), Some(_)) => return None,
+        (port, None) | (None, port) => port.unwrap_or(22),
+    };
+    if let Some(copy_args) = from.copy_command().or_else(|| to.copy_command()) {
+        let paced = copy_args.iter().any(|arg| arg.contains("{bwlimit}"));
+        // The copy could not be held to the limit, so the archive is streamed and paced instead
+        if bandwidth_limit.is_some() && !paced {
+            log::warn!("The transport copy command has no {{bwlimit}}, streaming through exec");
+            return None;
+        }
+        let mut template_args: Vec<String> = Vec::new();
+        for arg in copy_args {
+            // Arguments naming the limit are left out when there is none
+            if arg.contains("{bwlimit}") && bandwidth_limit.is_none() {
+                continue;
+            }
+            let bwlimit = bandwidth_limit
+                .map(|limit| limit.to_string())
+                .unwrap_or_default();
+            template_args.push(
+                arg.replace("{from}", &from_path)
+                    .replace("{to}", &to_path)
+                    .replace("{bwlimit}", &bwlimit),
+            );
+        }
+        return Some(template_args);
+    }
+
+    let mut scp_args: Vec<String> = Vec::new();
+    scp_args.push("scp".to_string());
+    scp_args.push("-P".to_string());
+    scp_args.push(port.to_string());
+    if let Some(bandwidth_limit) = bandwidth_limit {
+        scp_args.push("-l".to_string());
+        scp_args.push(bandwidth_limit.to_string());
+    }
+    scp_args.push("-r".to_string());
+    scp_args.push(from_path);
+    scp_args.push(to_path);
+    return Some(scp_args);
+}
+
+fn shell_quote(arg: &str) -> String {
+    let is_plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:@%+,".contains(c));
+    if is_plain {
+        return arg.to_string();
+    }
+    return format!("'{}'", arg.replace('\'', "'\\''"));
+}
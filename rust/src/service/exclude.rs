@@ -0,0 +1,105 @@
+// Follows tar --exclude: a pattern matches a path when it matches any run of its components,
+// and `*` may cross a `/`. Excluding a directory excludes everything under it.
+pub fn is_excluded(relative_path: &str, patterns: &[String]) -> bool {
+    let components: Vec<&str> = relative_path
+        .split('/')
+        .filter(|component| !component.is_empty() && *component != ".")
+        .collect();
+    for end in 1..=components.len() {
+        for start in 0..end {
+            let candidate = components[start..end].join("/");
+            if patterns
+                .iter()
+                .any(|pattern| glob_match(pattern.as_bytes(), candidate.as_bytes()))
+            {
+                return true;
+            }
+        }
+    }
+    return false;
+}
+
+pub fn tar_args(patterns: &[String]) -> Vec<String> {
+    return patterns
+        .iter()
+        .map(|pattern| format!("--exclude={}", pattern))
+        .collect();
+}
+
+// Supports `*` and `?`, enough for the patterns tar users reach for
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == b'*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    while p < pattern.len() && pattern[p] == b'*' {
+        p += 1;
+    }
+    return p == pattern.len();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn excluded(path: &str, pattern: &str) -> bool {
+        return is_excluded(path, &[pattern.to_string()]);
+    }
+
+    #[test]
+    fn names_match_at_any_depth() {
+        assert!(excluded("target", "target"));
+        assert!(excluded("crates/app/target/debug/app", "target"));
+        assert!(excluded("logs/today.log", "*.log"));
+        assert!(excluded("a/b.txt", "?.txt"));
+        assert!(!excluded("ab.txt", "?.txt"));
+        assert!(!excluded("targets", "target"));
+    }
+
+    #[test]
+    fn double_star_is_a_star_crossing_folders() {
+        assert!(excluded("a/b/c", "a/**/c"));
+        assert!(excluded("a/b/x/c/file", "a/**/c"));
+        assert!(!excluded("a/c", "a/**/c"));
+        assert!(excluded("a/c", "**/c"));
+        assert!(excluded("anything/at/all", "**"));
+        assert!(excluded("src/net/tcp.rs", "src*.rs"));
+    }
+
+    // tar names members without a leading slash and never with a trailing one
+    #[test]
+    fn anchored_and_trailing_slash_patterns_match_nothing() {
+        assert!(!excluded("build/x", "/build"));
+        assert!(!excluded("build", "/build"));
+        assert!(!excluded("build/x", "build/"));
+        assert!(!excluded("src/build", "build/"));
+    }
+
+    #[test]
+    fn patterns_with_a_slash_match_a_run_of_components() {
+        assert!(excluded("src/build/out", "src/build"));
+        assert!(excluded("app/src/build", "src/build"));
+        assert!(!excluded("build", "build/*"));
+        assert!(excluded("build/x", "build/*"));
+        assert!(!excluded("src/other/build", "src/build"));
+    }
+
+    #[test]
+    fn dot_and_empty_components_are_ignored() {
+        assert!(excluded("./build//x", "build/x"));
+        assert!(!excluded("", "*"));
+    }
+}
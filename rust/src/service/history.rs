@@ -0,0 +1,131 @@
+use crate::model::folder::Folder;
+use crate::model::history::{HistoryEntry, HistoryResult};
+use crate::model::options::SyncOptions;
+use crate::model::report::SyncReport;
+use crate::service::output;
+use chrono::{SecondsFormat, Utc};
+use home::home_dir;
+use log::warn;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Mutex, OnceLock};
+
+// Parallel syncs append from several threads
+static APPEND: Mutex<()> = Mutex::new(());
+static HOST: OnceLock<String> = OnceLock::new();
+
+pub fn history_path() -> PathBuf {
+    let home_dir = home_dir().expect("No Home Dir");
+    return Path::new(&home_dir.as_os_str()).join(".dirsync_history.jsonl");
+}
+
+pub fn user() -> String {
+    return env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_default();
+}
+
+// Always this machine's name, so hostname is started directly instead of through the runner.
+// Empty when neither hostname nor uname can tell.
+pub fn host() -> String {
+    return HOST
+        .get_or_init(|| {
+            [vec!["hostname"], vec!["uname", "-n"]]
+                .into_iter()
+                .find_map(|cmd_args| {
+                    let output = Command::new(cmd_args[0])
+                        .args(&cmd_args[1..])
+                        .output()
+                        .ok()?;
+                    let host = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                    (output.status.success() && !host.is_empty()).then_some(host)
+                })
+                .unwrap_or_default()
+        })
+        .clone();
+}
+
+// Records one finished sync, who, where and when are filled in here
+pub fn record(
+    (from_folder, from): (&Folder, String),
+    (to_folder, to): (&Folder, String),
+    relative_path: Option<String>,
+    mode: &str,
+    options: &SyncOptions,
+    result: &Result<SyncReport, String>,
+    duration_ms: u64,
+) -> () {
+    let (history_result, files, bytes, error) = match result {
+        Ok(report) if report.skipped => (HistoryResult::Skipped, report.files, report.bytes, None),
+        Ok(report) => (HistoryResult::Ok, report.files, report.bytes, None),
+        Err(err) => (HistoryResult::Failed, 0, 0, Some(err.clone())),
+    };
+    append(&HistoryEntry {
+        time: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        user: user(),
+        host: host(),
+        direction: direction(options),
+        link: options.link.clone(),
+        from_folder: from_folder.name.clone(),
+        to_folder: to_folder.name.clone(),
+        from,
+        to,
+        relative_path,
+        mode: mode.to_string(),
+        files,
+        bytes,
+        duration_ms,
+        result: history_result,
+        error,
+    });
+}
+
+// What the daemon ran, otherwise the command
+pub fn direction(options: &SyncOptions) -> String {
+    return options
+        .direction
+        .map(|direction| direction.name().to_string())
+        .unwrap_or_else(output::cmd);
+}
+
+// History is best effort, a sync never fails because it could not be recorded
+pub fn append(entry: &HistoryEntry) -> () {
+    let _guard = APPEND
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let line = serde_json::to_string(entry).expect("Error serializing history");
+    let appended = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(history_path())
+        .and_then(|mut file| writeln!(file, "{}", line));
+    if let Err(err) = appended {
+        warn!("Unable to record history: {}", err);
+    }
+}
+
+pub fn read() -> Result<Vec<HistoryEntry>, String> {
+    let path = history_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let file =
+        fs::read_to_string(&path).map_err(|err| format!("Failed to Read History: {}", err))?;
+    let mut entries = Vec::new();
+    for line in file.lines().filter(|line| !line.trim().is_empty()) {
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(err) => warn!("Skipping unreadable history line: {}", err),
+        }
+    }
+    return Ok(entries);
+}
+
+pub fn matches(entry: &HistoryEntry, target: &str) -> bool {
+    return entry.link.as_deref() == Some(target)
+        || entry.from_folder == target
+        || entry.to_folder == target;
+}
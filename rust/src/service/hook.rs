@@ -0,0 +1,115 @@
+use crate::model::folder::Folder;
+use crate::model::hook::HookStage;
+use crate::model::options::SyncOptions;
+use crate::model::ssh::SshServer;
+use crate::service::core;
+use crate::service::endpoint;
+use crate::service::history;
+use log::{info, warn};
+use std::collections::HashMap;
+
+struct Hook<'a> {
+    scope: &'static str,
+    role: &'static str,
+    // Where the command runs, locally or over ssh
+    folder: &'a Folder,
+    command: &'a str,
+    abort: bool,
+}
+
+// Folder hooks run on their own folder. The link's pre_sync runs on the source and its
+// post_sync on the destination, so a build precedes a push and an install follows a pull.
+pub fn run(
+    stage: HookStage,
+    from_folder: &Folder,
+    to_folder: &Folder,
+    ssh_servers: &HashMap<String, SshServer>,
+    relative_paths: &[Option<String>],
+    options: &SyncOptions,
+) -> Result<(), String> {
+    let folder_hooks = [(from_folder, "source"), (to_folder, "destination")]
+        .into_iter()
+        .filter_map(|(folder, role)| {
+            Some(Hook {
+                scope: "folder",
+                role,
+                folder,
+                command: folder.hooks.command(stage)?,
+                abort: folder.hooks.pre_sync_abort,
+            })
+        });
+    let link_hook = options.hooks.command(stage).map(|command| {
+        let (folder, role) = match stage {
+            HookStage::PreSync => (from_folder, "source"),
+            HookStage::PostSync => (to_folder, "destination"),
+        };
+        Hook {
+            scope: "link",
+            role,
+            folder,
+            command,
+            abort: options.hooks.pre_sync_abort,
+        }
+    });
+    let hooks: Vec<Hook> = match stage {
+        HookStage::PreSync => folder_hooks.chain(link_hook).collect(),
+        HookStage::PostSync => link_hook.into_iter().chain(folder_hooks.rev()).collect(),
+    };
+
+    let relative_paths: Vec<String> = relative_paths.iter().flatten().cloned().collect();
+    let mut failures = Vec::new();
+    for hook in hooks {
+        let env = [
+            ("DSYNC_HOOK", stage.name().to_string()),
+            ("DSYNC_SCOPE", hook.scope.to_string()),
+            ("DSYNC_ROLE", hook.role.to_string()),
+            ("DSYNC_DIRECTION", history::direction(options)),
+            ("DSYNC_LINK", options.link.clone().unwrap_or_default()),
+            ("DSYNC_FOLDER", hook.folder.path.clone()),
+            ("DSYNC_FROM_FOLDER", from_folder.name.clone()),
+            ("DSYNC_FROM_PATH", from_folder.path.clone()),
+            ("DSYNC_TO_FOLDER", to_folder.name.clone()),
+            ("DSYNC_TO_PATH", to_folder.path.clone()),
+            ("DSYNC_RELATIVE_PATHS", relative_paths.join(" ")),
+        ];
+        let mut hook_args: Vec<String> = Vec::new();
+        hook_args.push("env".to_string());
+        for (key, value) in env {
+            hook_args.push(format!("{}={}", key, value));
+        }
+        hook_args.push("sh".to_string());
+        hook_args.push("-c".to_string());
+        // Storage services have no directory to run in, their hooks run where dsync does
+        match hook.folder.target.is_storage() {
+            true => hook_args.push(hook.command.to_string()),
+            false => hook_args.push(format!("cd \"$DSYNC_FOLDER\" && {}", hook.command)),
+        }
+        let hook_args = endpoint::shell(hook.folder, ssh_servers)?.exec(hook_args)?;
+
+        info!(
+            "Running {} {} hook on {}: {}",
+            hook.scope,
+            stage.name(),
+            hook.folder.name,
+            hook.command
+        );
+        let failure_msg = match stage {
+            HookStage::PreSync => "Pre Sync Hook Failed",
+            HookStage::PostSync => "Post Sync Hook Failed",
+        };
+        let Err(err) = core::run(stage.name(), hook_args, failure_msg) else {
+            continue;
+        };
+        let failure = format!("{} on {}: {}", err, hook.folder.name, hook.command);
+        match stage {
+            HookStage::PreSync if hook.abort => return Err(format!("{}, sync aborted", failure)),
+            HookStage::PreSync => warn!("{}", failure),
+            HookStage::PostSync => failures.push(failure),
+        }
+    }
+    // A failed post_sync leaves the files synced, but still fails the run
+    if !failures.is_empty() {
+        return Err(failures.join("; "));
+    }
+    return Ok(());
+}
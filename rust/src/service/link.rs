@@ -1,13 +1,33 @@
+use crate::model::hook::Hooks;
 use crate::model::link::Link;
-use std::collections::HashMap;
+use crate::model::options::SyncOptions;
 
-pub fn get(name: String, links: HashMap<String, Link>) -> Option<Link> {
-    let mut found = None;
-    for link in links {
-        if link.0 == name {
-            found = Some(link.1);
-            break;
-        }
+// Link settings layered over the options given on the command line
+pub fn options(link: &Link, cli_options: &SyncOptions) -> SyncOptions {
+    SyncOptions {
+        link: Some(link.name.clone()),
+        compression: link.compression.clone().or(cli_options.compression.clone()),
+        transfer: link.transfer.or(cli_options.transfer),
+        checksum: link.checksum.or(cli_options.checksum),
+        verify: cli_options.verify || link.verify,
+        low_priority: cli_options.low_priority.or(link.low_priority),
+        jobs: cli_options.jobs.or(link.jobs),
+        bundle: cli_options.bundle || link.bundle,
+        exclude: [link.exclude.clone(), cli_options.exclude.clone()].concat(),
+        hooks: hooks(&link.hooks, &cli_options.hooks),
+        ..cli_options.clone()
     }
-    return found;
+}
+
+// Each of the link's hooks wins over the caller's, a pre_sync keeps its own abort setting
+fn hooks(link_hooks: &Hooks, cli_hooks: &Hooks) -> Hooks {
+    let pre_sync_abort = match link_hooks.pre_sync {
+        Some(_) => link_hooks.pre_sync_abort,
+        None => cli_hooks.pre_sync_abort,
+    };
+    return Hooks {
+        pre_sync: link_hooks.pre_sync.clone().or(cli_hooks.pre_sync.clone()),
+        post_sync: link_hooks.post_sync.clone().or(cli_hooks.post_sync.clone()),
+        pre_sync_abort,
+    };
 }
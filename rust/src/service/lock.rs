@@ -0,0 +1,250 @@
+use crate::model::folder::Folder;
+use crate::model::lock::LockOwner;
+use crate::model::ssh::SshServer;
+use crate::service::endpoint;
+use crate::service::history;
+use crate::service::runner;
+use crate::service::tar::fnv1a;
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use log::{debug, info, warn};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use std::collections::HashMap;
+use std::env;
+use std::process;
+use std::thread;
+
+// A lock from another host this old is taken over, its owner cannot be checked from here
+const STALE_AFTER_HOURS: i64 = 24;
+const WAIT_SECONDS: u64 = 2;
+
+// In the work folder of the destination, or next to it when it has none
+pub fn lock_path(
+    folder: &Folder,
+    path: &str,
+    work_folder: &Option<Folder>,
+    ssh_servers: &HashMap<String, SshServer>,
+) -> Result<String, String> {
+    let work_folder = endpoint::get(folder, ssh_servers)?
+        .work_folder(work_folder)
+        .clone();
+    let lock_name = format!(
+        "dsync-{:016x}.lock",
+        fnv1a(&format!(
+            "{}:{}",
+            folder.ssh_key.clone().unwrap_or_default(),
+            path.trim_end_matches('/')
+        ))
+    );
+    let lock_path = match work_folder {
+        Some(work_folder) => format!("{}/{}", work_folder.path, lock_name),
+        // Storage folders lock on this machine, where their path means nothing
+        None if folder.target.is_storage() => {
+            format!("{}/.{}", env::temp_dir().display(), lock_name)
+        }
+        None => {
+            let parent = path
+                .trim_end_matches('/')
+                .rsplit_once('/')
+                .map(|(parent, _)| parent);
+            format!("{}/.{}", parent.unwrap_or("."), lock_name)
+        }
+    };
+    return Ok(lock_path);
+}
+
+// Creates the lock file with noclobber, so only one of several racing syncs gets it
+pub fn acquire(
+    folder: &Folder,
+    lock_path: &str,
+    ssh_servers: &HashMap<String, SshServer>,
+    wait: bool,
+) -> Result<(), String> {
+    let owner = LockOwner {
+        user: history::user(),
+        host: history::host(),
+        pid: process::id(),
+        since: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+    };
+    let owner_line = serde_json::to_string(&owner).expect("Error serializing lock");
+    let mut create_args: Vec<String> = Vec::new();
+    create_args.push("sh".to_string());
+    create_args.push("-c".to_string());
+    create_args.push("set -C; printf '%s\\n' \"$1\" > \"$2\"".to_string());
+    create_args.push("sh".to_string());
+    create_args.push(owner_line);
+    create_args.push(lock_path.to_string());
+    let create_args = endpoint::shell(folder, ssh_servers)?.exec(create_args)?;
+
+    let mut waiting = false;
+    let mut unreadable = false;
+    let mut attempts = 0;
+    loop {
+        if run_quiet(&create_args).is_some() {
+            debug!("Locked {}", lock_path);
+            return Ok(());
+        }
+        let Some(content) = read(folder, lock_path, ssh_servers) else {
+            // Released between the two commands, or the folder cannot hold a lock at all
+            attempts += 1;
+            if attempts >= 3 {
+                return Err(format!("Unable to create lock: {}", lock_path));
+            }
+            continue;
+        };
+        let holder: Option<LockOwner> = serde_json::from_str(content.trim()).ok();
+        let stale = match &holder {
+            Some(holder) => is_stale(holder),
+            // Seen twice, so not a lock still being written
+            None => unreadable,
+        };
+        if stale {
+            if take_over(folder, lock_path, content.trim(), ssh_servers) {
+                warn!("Removed stale lock {}: {}", lock_path, content.trim());
+            }
+            unreadable = false;
+            continue;
+        }
+        let Some(holder) = holder else {
+            unreadable = true;
+            thread::sleep(std::time::Duration::from_secs(1));
+            continue;
+        };
+        let held_by = format!(
+            "{}@{} (pid {}) since {}",
+            holder.user, holder.host, holder.pid, holder.since
+        );
+        if !wait {
+            return Err(format!(
+                "Destination is locked by {}, pass --wait to wait for it: {}",
+                held_by, lock_path
+            ));
+        }
+        if !waiting {
+            info!("Waiting for the lock held by {}", held_by);
+            waiting = true;
+        }
+        thread::sleep(std::time::Duration::from_secs(WAIT_SECONDS));
+    }
+}
+
+// Takes the locks in order, and gives back the ones already held when one is refused
+pub fn acquire_all(
+    locks: &[(&Folder, String)],
+    ssh_servers: &HashMap<String, SshServer>,
+    wait: bool,
+) -> Result<(), String> {
+    for (index, (folder, lock_path)) in locks.iter().enumerate() {
+        if let Err(err) = acquire(folder, lock_path, ssh_servers, wait) {
+            release_all(&locks[..index], ssh_servers);
+            return Err(err);
+        }
+    }
+    return Ok(());
+}
+
+pub fn release_all(locks: &[(&Folder, String)], ssh_servers: &HashMap<String, SshServer>) -> () {
+    for (folder, lock_path) in locks.iter().rev() {
+        release(folder, lock_path, ssh_servers);
+    }
+}
+
+pub fn release(folder: &Folder, lock_path: &str, ssh_servers: &HashMap<String, SshServer>) -> () {
+    let mut delete_args: Vec<String> = Vec::new();
+    delete_args.push("rm".to_string());
+    delete_args.push("-f".to_string());
+    delete_args.push(lock_path.to_string());
+    let delete_args =
+        endpoint::shell(folder, ssh_servers).and_then(|shell| shell.exec(delete_args));
+    if delete_args.ok().and_then(|args| run_quiet(&args)).is_none() {
+        warn!("Unable to remove lock {}", lock_path);
+    }
+}
+
+// Moves the lock aside and only removes it while it still holds the stale owner, so a sync that
+// took it over in the meantime keeps its lock. One that lost the race puts it back.
+const TAKE_OVER_SCRIPT: &str = r#"mv "$2" "$3" || exit 1
+if [ "$(cat "$3")" = "$1" ]; then
+    rm -f "$3"
+else
+    ln "$3" "$2"
+    rm -f "$3"
+    exit 1
+fi"#;
+
+fn take_over(
+    folder: &Folder,
+    lock_path: &str,
+    stale_content: &str,
+    ssh_servers: &HashMap<String, SshServer>,
+) -> bool {
+    let random_name: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(7)
+        .map(char::from)
+        .collect();
+    let mut take_over_args: Vec<String> = Vec::new();
+    take_over_args.push("sh".to_string());
+    take_over_args.push("-c".to_string());
+    take_over_args.push(TAKE_OVER_SCRIPT.to_string());
+    take_over_args.push("sh".to_string());
+    take_over_args.push(stale_content.to_string());
+    take_over_args.push(lock_path.to_string());
+    take_over_args.push(format!("{}.{}", lock_path, random_name));
+    let take_over_args =
+        endpoint::shell(folder, ssh_servers).and_then(|shell| shell.exec(take_over_args));
+    return take_over_args
+        .ok()
+        .and_then(|args| run_quiet(&args))
+        .is_some();
+}
+
+fn read(
+    folder: &Folder,
+    lock_path: &str,
+    ssh_servers: &HashMap<String, SshServer>,
+) -> Option<String> {
+    let mut read_args: Vec<String> = Vec::new();
+    read_args.push("cat".to_string());
+    read_args.push(lock_path.to_string());
+    let read_args = endpoint::shell(folder, ssh_servers)
+        .and_then(|shell| shell.exec(read_args))
+        .ok()?;
+    return run_quiet(&read_args);
+}
+
+// kill -0 cannot signal the processes of other users, ps still sees them
+const ALIVE_SCRIPT: &str = r#"kill -0 "$1" 2>/dev/null || ps -p "$1" >/dev/null 2>&1"#;
+
+// A dead process on this host, or an owner elsewhere gone for longer than any sync takes. A live
+// process on this host keeps its lock however long it runs, and so does any lock while this
+// host's name is unknown.
+fn is_stale(holder: &LockOwner) -> bool {
+    let host = history::host();
+    if host.is_empty() {
+        return false;
+    }
+    if holder.host == host {
+        let mut alive_args: Vec<String> = Vec::new();
+        alive_args.push("sh".to_string());
+        alive_args.push("-c".to_string());
+        alive_args.push(ALIVE_SCRIPT.to_string());
+        alive_args.push("sh".to_string());
+        alive_args.push(holder.pid.to_string());
+        return runner::get()
+            .output(&alive_args)
+            .map(|output| !output.status.success())
+            .unwrap_or(false);
+    }
+    return DateTime::parse_from_rfc3339(&holder.since)
+        .map(|since| Utc::now() - since.with_timezone(&Utc) > Duration::hours(STALE_AFTER_HOURS))
+        .unwrap_or(false);
+}
+
+fn run_quiet(cmd_args: &[String]) -> Option<String> {
+    let output = runner::get().output(cmd_args).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    return Some(String::from_utf8_lossy(&output.stdout).to_string());
+}
@@ -0,0 +1,110 @@
+use crate::service::output;
+use chrono::{SecondsFormat, Utc};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::{Mutex, OnceLock};
+
+// Target used for lines forwarded from spawned commands
+pub const SUBPROCESS_TARGET: &str = "subprocess";
+
+static CONSOLE_LEVEL: OnceLock<LevelFilter> = OnceLock::new();
+
+struct Logger {
+    console: LevelFilter,
+    file: Option<Mutex<File>>,
+}
+
+#[derive(Serialize)]
+struct FileRecord<'a> {
+    ts: String,
+    level: &'a str,
+    target: &'a str,
+    msg: String,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        return metadata.level() <= self.console || self.file.is_some();
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() <= self.console {
+            let msg = match (record.level(), record.target()) {
+                (_, SUBPROCESS_TARGET) => format!("  | {}", record.args()),
+                (Level::Warn, _) => format!("Warning: {}", record.args()),
+                _ => record.args().to_string(),
+            };
+            // stdout carries events in json mode, errors always go to stderr
+            if output::is_json() || record.level() <= Level::Warn {
+                eprintln!("{}", msg);
+            } else {
+                println!("{}", msg);
+            }
+        }
+
+        if let Some(file) = &self.file {
+            let line = FileRecord {
+                ts: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+                level: record.level().as_str(),
+                target: record.target(),
+                msg: record.args().to_string(),
+            };
+            let line = serde_json::to_string(&line).expect("Error serializing log record");
+            let mut file = file.lock().expect("Log file lock poisoned");
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = file.lock().expect("Log file lock poisoned").flush();
+        }
+    }
+}
+
+pub fn level_from_args(quiet: bool, verbose: u8) -> LevelFilter {
+    if quiet {
+        return LevelFilter::Warn;
+    }
+    return match verbose {
+        0 => LevelFilter::Info,
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+}
+
+// The log file always records everything, the console only what was asked for
+pub fn init(console: LevelFilter, log_file: &Option<String>) -> Result<(), String> {
+    let file = match log_file {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|err| format!("Error opening log file {}: {}", path, err))?;
+            Some(Mutex::new(file))
+        }
+        None => None,
+    };
+    let max_level = if file.is_some() {
+        LevelFilter::Trace
+    } else {
+        console
+    };
+    log::set_boxed_logger(Box::new(Logger { console, file }))
+        .map_err(|_| "A logger is already set".to_string())?;
+    CONSOLE_LEVEL.get_or_init(|| console);
+    log::set_max_level(max_level);
+    return Ok(());
+}
+
+// Subprocess output is only worth capturing when some sink will show it
+pub fn streams_subprocess() -> bool {
+    return log::max_level() >= LevelFilter::Debug;
+}
+
+pub fn console_level() -> LevelFilter {
+    return *CONSOLE_LEVEL.get().unwrap_or(&LevelFilter::Info);
+}
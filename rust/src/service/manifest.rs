@@ -0,0 +1,306 @@
+use crate::model::{
+    checksum::ChecksumAlgorithm,
+    event::EventKind,
+    folder::Folder,
+    manifest::{DifferingEntry, Manifest, ManifestDiff, ManifestEntry},
+    ssh::SshServer,
+};
+use crate::service::checksum;
+use crate::service::endpoint;
+use crate::service::exclude;
+use crate::service::output;
+use crate::service::runner;
+use log::{debug, info, warn};
+use std::collections::HashMap;
+
+fn run_capture(cmd_args: &[String]) -> Result<String, String> {
+    let cmd_output = runner::get()
+        .output(cmd_args)
+        .map_err(|err| format!("Failed to Build Manifest: {}", err))?;
+    if !cmd_output.status.success() {
+        return Err(format!(
+            "Failed to Build Manifest: {}",
+            String::from_utf8_lossy(&cmd_output.stderr).trim()
+        ));
+    }
+    return Ok(String::from_utf8_lossy(&cmd_output.stdout).to_string());
+}
+
+fn relative(path: &str, root: &str) -> String {
+    let path = path.strip_prefix(root).unwrap_or(path);
+    return path.trim_start_matches('/').to_string();
+}
+
+pub fn stat_args(path: String) -> Vec<String> {
+    let mut stat_args: Vec<String> = Vec::new();
+    stat_args.push("find".to_string());
+    stat_args.push(path);
+    stat_args.push("-type".to_string());
+    stat_args.push("f".to_string());
+    stat_args.push("-exec".to_string());
+    stat_args.push("stat".to_string());
+    stat_args.push("-c".to_string());
+    stat_args.push("%s %a %Y %n".to_string());
+    stat_args.push("{}".to_string());
+    stat_args.push("+".to_string());
+    return stat_args;
+}
+
+// Sizes, modes and mtimes come from stat, hashes from the checksum tool unless it is "none"
+pub fn build(
+    folder: &Folder,
+    path: &str,
+    ssh_servers: &HashMap<String, SshServer>,
+    checksum_algorithm: ChecksumAlgorithm,
+) -> Result<Manifest, String> {
+    let mut manifest = Manifest::default();
+    let stat_output =
+        run_capture(&endpoint::get(folder, ssh_servers)?.exec(stat_args(path.to_string()))?)?;
+    for line in stat_output.lines() {
+        let mut fields = line.splitn(4, ' ');
+        let (Some(size), Some(mode), Some(mtime), Some(file_path)) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            warn!("Unexpected stat output: {}", line);
+            continue;
+        };
+        manifest.entries.insert(
+            relative(file_path, path),
+            ManifestEntry {
+                size: size.parse().unwrap_or(0),
+                mode: mode.to_string(),
+                mtime: mtime.parse().unwrap_or(0),
+                hash: None,
+            },
+        );
+    }
+
+    if let Some(program) = checksum_algorithm.program() {
+        let checksum_output = run_capture(
+            &endpoint::get(folder, ssh_servers)?
+                .exec(checksum::tree_args(program, path.to_string()))?,
+        )?;
+        for line in checksum_output.lines() {
+            if let Some((hash, file_path)) = line.split_once("  ") {
+                if let Some(entry) = manifest.entries.get_mut(&relative(file_path, path)) {
+                    entry.hash = Some(hash.to_string());
+                }
+            }
+        }
+    }
+    debug!("Manifest of {} has {} files", path, manifest.entries.len());
+    return Ok(manifest);
+}
+
+// tar extracts with the umask of whoever runs it, so the destination may lose group and other
+// bits the source has. Bits the source lacks or a different owner still count as a mismatch.
+fn mode_matches(source_mode: &str, destination_mode: &str) -> bool {
+    let (Ok(source_mode), Ok(destination_mode)) = (
+        u32::from_str_radix(source_mode, 8),
+        u32::from_str_radix(destination_mode, 8),
+    ) else {
+        return source_mode == destination_mode;
+    };
+    return destination_mode & !source_mode == 0 && destination_mode & 0o700 == source_mode & 0o700;
+}
+
+pub fn compare(source: &Manifest, destination: &Manifest) -> ManifestDiff {
+    let mut diff = ManifestDiff::default();
+    for (path, source_entry) in source.entries.iter() {
+        let Some(destination_entry) = destination.entries.get(path) else {
+            diff.missing.push(path.clone());
+            continue;
+        };
+        let mut fields = Vec::new();
+        if source_entry.size != destination_entry.size {
+            fields.push("size");
+        }
+        if !mode_matches(&source_entry.mode, &destination_entry.mode) {
+            fields.push("mode");
+        }
+        if source_entry.mtime != destination_entry.mtime {
+            fields.push("mtime");
+        }
+        if source_entry.hash != destination_entry.hash {
+            fields.push("hash");
+        }
+        if !fields.is_empty() {
+            diff.differing.push(DifferingEntry {
+                path: path.clone(),
+                fields,
+            });
+        }
+    }
+    for path in destination.entries.keys() {
+        if !source.entries.contains_key(path) {
+            diff.extra.push(path.clone());
+        }
+    }
+    return diff;
+}
+
+pub fn verify(
+    from_folder: &Folder,
+    from_path: &str,
+    to_folder: &Folder,
+    to_path: &str,
+    ssh_servers: &HashMap<String, SshServer>,
+    checksum_algorithm: ChecksumAlgorithm,
+    exclude_patterns: &[String],
+) -> Result<(), String> {
+    let source = build(from_folder, from_path, ssh_servers, checksum_algorithm)?;
+    let destination = build(to_folder, to_path, ssh_servers, checksum_algorithm)?;
+    let source = exclude(source, exclude_patterns);
+    let destination = exclude(destination, exclude_patterns);
+    return report(from_path, to_path, &compare(&source, &destination));
+}
+
+// Drops the entries matching a link's exclude patterns
+pub fn exclude(mut manifest: Manifest, patterns: &[String]) -> Manifest {
+    if !patterns.is_empty() {
+        manifest
+            .entries
+            .retain(|path, _| !exclude::is_excluded(path, patterns));
+    }
+    return manifest;
+}
+
+pub fn report(from_path: &str, to_path: &str, diff: &ManifestDiff) -> Result<(), String> {
+    output::emit(EventKind::Verify {
+        from: from_path.to_string(),
+        to: to_path.to_string(),
+        matched: diff.is_empty(),
+        missing: diff.missing.clone(),
+        extra: diff.extra.clone(),
+        differing: diff.differing.clone(),
+    });
+    if diff.is_empty() {
+        info!("Verified: {} matches {}", to_path, from_path);
+        return Ok(());
+    }
+    for path in diff.missing.iter() {
+        warn!("Missing from {}: {}", to_path, path);
+    }
+    for path in diff.extra.iter() {
+        warn!("Extra in {}: {}", to_path, path);
+    }
+    for entry in diff.differing.iter() {
+        warn!("Differs ({}): {}", entry.fields.join(", "), entry.path);
+    }
+    return Err(format!(
+        "{} does not match {}: {} missing, {} extra, {} differing",
+        to_path,
+        from_path,
+        diff.missing.len(),
+        diff.extra.len(),
+        diff.differing.len()
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(size: u64, mode: &str, mtime: i64, hash: Option<&str>) -> ManifestEntry {
+        return ManifestEntry {
+            size,
+            mode: mode.to_string(),
+            mtime,
+            hash: hash.map(str::to_string),
+        };
+    }
+
+    fn manifest(entries: Vec<(&str, ManifestEntry)>) -> Manifest {
+        let mut manifest = Manifest::default();
+        for (path, entry) in entries {
+            manifest.entries.insert(path.to_string(), entry);
+        }
+        return manifest;
+    }
+
+    fn fields(diff: &ManifestDiff, path: &str) -> Vec<&'static str> {
+        return diff
+            .differing
+            .iter()
+            .find(|entry| entry.path == path)
+            .map(|entry| entry.fields.clone())
+            .unwrap_or_default();
+    }
+
+    #[test]
+    fn identical_manifests_match() {
+        let source = manifest(vec![("a", entry(3, "644", 10, Some("h")))]);
+        let destination = manifest(vec![("a", entry(3, "644", 10, Some("h")))]);
+        assert!(compare(&source, &destination).is_empty());
+    }
+
+    #[test]
+    fn missing_and_extra_paths_are_listed() {
+        let source = manifest(vec![
+            ("kept", entry(1, "644", 1, None)),
+            ("gone", entry(1, "644", 1, None)),
+        ]);
+        let destination = manifest(vec![
+            ("kept", entry(1, "644", 1, None)),
+            ("new", entry(1, "644", 1, None)),
+        ]);
+        let diff = compare(&source, &destination);
+        assert_eq!(diff.missing, vec!["gone".to_string()]);
+        assert_eq!(diff.extra, vec!["new".to_string()]);
+        assert!(diff.differing.is_empty());
+    }
+
+    #[test]
+    fn each_differing_field_is_named() {
+        let source = manifest(vec![("a", entry(3, "644", 10, Some("h")))]);
+        let destination = manifest(vec![("a", entry(4, "755", 11, Some("g")))]);
+        let diff = compare(&source, &destination);
+        assert_eq!(fields(&diff, "a"), vec!["size", "mode", "mtime", "hash"]);
+    }
+
+    #[test]
+    fn modes_narrowed_by_the_umask_match() {
+        let source = manifest(vec![
+            ("group", entry(1, "664", 5, None)),
+            ("shared", entry(1, "777", 5, None)),
+            ("script", entry(1, "775", 5, None)),
+        ]);
+        let destination = manifest(vec![
+            ("group", entry(1, "644", 5, None)),
+            ("shared", entry(1, "700", 5, None)),
+            ("script", entry(1, "755", 5, None)),
+        ]);
+        assert!(compare(&source, &destination).is_empty());
+    }
+
+    #[test]
+    fn modes_gaining_bits_or_changing_the_owner_differ() {
+        let source = manifest(vec![
+            ("wider", entry(1, "644", 5, None)),
+            ("executable", entry(1, "755", 5, None)),
+            ("setuid", entry(1, "755", 5, None)),
+        ]);
+        let destination = manifest(vec![
+            ("wider", entry(1, "666", 5, None)),
+            ("executable", entry(1, "655", 5, None)),
+            ("setuid", entry(1, "4755", 5, None)),
+        ]);
+        let diff = compare(&source, &destination);
+        assert_eq!(fields(&diff, "wider"), vec!["mode"]);
+        assert_eq!(fields(&diff, "executable"), vec!["mode"]);
+        assert_eq!(fields(&diff, "setuid"), vec!["mode"]);
+    }
+
+    #[test]
+    fn mtimes_still_have_to_match_when_the_mode_is_narrowed() {
+        let source = manifest(vec![("a", entry(1, "664", 5, None))]);
+        let destination = manifest(vec![("a", entry(1, "644", 6, None))]);
+        assert_eq!(fields(&compare(&source, &destination), "a"), vec!["mtime"]);
+    }
+
+    #[test]
+    fn modes_that_are_not_octal_are_compared_as_text() {
+        assert!(mode_matches("rw-r--r--", "rw-r--r--"));
+        assert!(!mode_matches("rw-rw-r--", "rw-r--r--"));
+    }
+}
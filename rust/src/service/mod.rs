@@ -1,7 +1,28 @@
-pub mod cli;
+pub mod archive;
+pub mod bisync;
+pub mod checksum;
+pub mod compression;
 pub mod config;
 pub mod core;
+pub mod daemon;
+pub mod endpoint;
+pub mod exclude;
 pub mod folder;
+pub mod history;
+pub mod hook;
 pub mod link;
+pub mod lock;
+pub mod logger;
+pub mod manifest;
+pub mod output;
+pub mod progress;
+pub mod prompt;
+pub mod runner;
+pub mod s3;
 pub mod ssh;
+pub mod syncer;
 pub mod tar;
+pub mod throttle;
+pub mod transfer;
+pub mod watch;
+pub mod webdav;
@@ -0,0 +1,50 @@
+use crate::model::event::{Event, EventKind, SCHEMA_VERSION};
+use chrono::{SecondsFormat, Utc};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+static JSON: AtomicBool = AtomicBool::new(false);
+static CMD: OnceLock<String> = OnceLock::new();
+
+pub fn init(json: bool, cmd: &str) -> () {
+    JSON.store(json, Ordering::Relaxed);
+    CMD.get_or_init(|| cmd.to_string());
+}
+
+pub fn cmd() -> String {
+    return CMD.get().cloned().unwrap_or_default();
+}
+
+pub fn is_json() -> bool {
+    return JSON.load(Ordering::Relaxed);
+}
+
+// Text that must reach the user whatever the verbosity, such as prompts
+pub fn text(msg: &str) -> () {
+    if is_json() {
+        eprintln!("{}", msg);
+    } else {
+        println!("{}", msg);
+    }
+}
+
+pub fn emit(kind: EventKind) -> () {
+    if !is_json() {
+        return;
+    }
+    let event = Event {
+        v: SCHEMA_VERSION,
+        ts: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        cmd: cmd(),
+        kind,
+    };
+    let line = serde_json::to_string(&event).expect("Error serializing event");
+    println!("{}", line);
+}
+
+pub fn error(msg: &str) -> () {
+    emit(EventKind::Error {
+        message: msg.to_string(),
+    });
+    log::error!("{}", msg);
+}
@@ -0,0 +1,122 @@
+use crate::service::logger;
+use crate::service::output;
+use crate::service::runner;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use log::LevelFilter;
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(1000);
+
+#[derive(Clone, Copy, Debug)]
+pub enum Measure {
+    // `wc -c <file>`, bytes
+    File,
+    // `du -sck <dir>...`, kibibytes
+    Tree,
+}
+
+pub enum Probe {
+    // Polled command printing a size, run through ssh for remote paths
+    Command(Vec<String>, Measure),
+    // Bytes we move ourselves, such as a streamed archive
+    Counter(Arc<AtomicU64>),
+}
+
+impl Probe {
+    fn read(&self) -> Option<u64> {
+        match self {
+            Probe::Command(probe, measure_kind) => measure(probe, *measure_kind),
+            Probe::Counter(counter) => Some(counter.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+pub struct Tracker {
+    bar: ProgressBar,
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+// Bars only make sense for a person watching a terminal at the default level
+pub fn enabled() -> bool {
+    return !output::is_json()
+        && logger::console_level() == LevelFilter::Info
+        && io::stderr().is_terminal();
+}
+
+pub fn probe_args(measure: Measure, paths: Vec<String>) -> Vec<String> {
+    let mut probe_args: Vec<String> = Vec::new();
+    match measure {
+        Measure::File => {
+            probe_args.push("wc".to_string());
+            probe_args.push("-c".to_string());
+        }
+        Measure::Tree => {
+            probe_args.push("du".to_string());
+            probe_args.push("-sck".to_string());
+        }
+    }
+    probe_args.extend(paths);
+    return probe_args;
+}
+
+pub fn measure(probe: &[String], measure: Measure) -> Option<u64> {
+    let probe_output = runner::get().output(probe).ok()?;
+    if !probe_output.status.success() {
+        return None;
+    }
+    // Several paths end with a total line
+    let value = String::from_utf8_lossy(&probe_output.stdout)
+        .lines()
+        .last()?
+        .split_whitespace()
+        .next()?
+        .parse::<u64>()
+        .ok()?;
+    return match measure {
+        Measure::File => Some(value),
+        Measure::Tree => Some(value * 1024),
+    };
+}
+
+pub fn start(label: &str, total: u64, probe: Probe) -> Tracker {
+    let bar = ProgressBar::with_draw_target(Some(total), ProgressDrawTarget::stderr());
+    bar.set_style(
+        ProgressStyle::with_template(
+            "{prefix:>9} [{bar:30}] {bytes}/{total_bytes} {binary_bytes_per_sec} ETA {eta}",
+        )
+        .expect("Invalid progress template")
+        .progress_chars("=> "),
+    );
+    bar.set_prefix(label.to_string());
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread_bar = bar.clone();
+    let thread_stop = stop.clone();
+    let handle = thread::spawn(move || {
+        while !thread_stop.load(Ordering::Relaxed) {
+            if let Some(done) = probe.read() {
+                // Archives and du rounding can overshoot the estimate
+                if done > thread_bar.length().unwrap_or(0) {
+                    thread_bar.set_length(done);
+                }
+                thread_bar.set_position(done);
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    });
+
+    return Tracker { bar, stop, handle };
+}
+
+impl Tracker {
+    pub fn finish(self) -> () {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.handle.join();
+        self.bar.finish();
+    }
+}
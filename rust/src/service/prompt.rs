@@ -0,0 +1,28 @@
+use crate::model::bisync::ConflictResolution;
+use std::sync::{Arc, RwLock};
+
+// Questions a sync asks before going on. The library asks nobody unless a prompt is set, so an
+// unforced sync is declined and conflicts are left as they are.
+pub trait Prompt: Send + Sync {
+    // Whether the planned syncs may run
+    fn confirm(&self) -> bool;
+
+    // How to settle a file changed on both sides, None leaves it as is
+    fn resolve_conflict(&self, path: &str) -> Option<ConflictResolution>;
+}
+
+static PROMPT: RwLock<Option<Arc<dyn Prompt>>> = RwLock::new(None);
+
+// Replaces the prompt for the whole process, None goes back to asking nobody
+pub fn set(prompt: Option<Arc<dyn Prompt>>) -> () {
+    *PROMPT
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = prompt;
+}
+
+pub fn get() -> Option<Arc<dyn Prompt>> {
+    return PROMPT
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone();
+}
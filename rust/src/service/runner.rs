@@ -0,0 +1,299 @@
+use crate::service::logger::{self, SUBPROCESS_TARGET};
+use crate::service::throttle;
+use log::{debug, error, LevelFilter};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+// Every process dsync starts goes through the runner, so a fake one can stand in for ssh hosts
+pub trait CommandRunner: Send + Sync {
+    // Runs to completion with stdout and stderr captured
+    fn output(&self, cmd_args: &[String]) -> io::Result<Output>;
+
+    // Runs to completion with its output going to the log
+    fn run(&self, cmd_args: &[String]) -> io::Result<ExitStatus>;
+
+    // Pumps the stdout of cmd_args into pipe_to, counting and pacing the bytes
+    fn pipe(
+        &self,
+        cmd_args: &[String],
+        pipe_to: &[String],
+        streamed: &AtomicU64,
+        bandwidth_limit: Option<u32>,
+    ) -> io::Result<ExitStatus>;
+}
+
+// Bytes of arguments given to one command, well under any system's limit
+const BATCH_BYTES: usize = 64 * 1024;
+
+static RUNNER: RwLock<Option<Arc<dyn CommandRunner>>> = RwLock::new(None);
+
+// Replaces the runner for the whole process, None goes back to spawning processes
+pub fn set(runner: Option<Arc<dyn CommandRunner>>) -> () {
+    *RUNNER
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = runner;
+}
+
+// Splits items into batches, one command each, whose arguments stay under BATCH_BYTES
+pub fn batches<T>(items: &[T], size: impl Fn(&T) -> usize) -> Vec<&[T]> {
+    let mut batches = Vec::new();
+    let (mut start, mut bytes) = (0, 0);
+    for (index, item) in items.iter().enumerate() {
+        if index > start && bytes + size(item) > BATCH_BYTES {
+            batches.push(&items[start..index]);
+            (start, bytes) = (index, 0);
+        }
+        bytes += size(item);
+    }
+    if start < items.len() {
+        batches.push(&items[start..]);
+    }
+    return batches;
+}
+
+pub fn get() -> Arc<dyn CommandRunner> {
+    let runner = RUNNER
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    return match runner.as_ref() {
+        Some(runner) => runner.clone(),
+        None => Arc::new(SystemRunner),
+    };
+}
+
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn output(&self, cmd_args: &[String]) -> io::Result<Output> {
+        return build_cmd(cmd_args).output();
+    }
+
+    fn run(&self, cmd_args: &[String]) -> io::Result<ExitStatus> {
+        let mut child = build_cmd(cmd_args)
+            .stdout(stdout_target())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let stderr = watch_stderr(&mut child);
+        if let Some(stdout) = child.stdout.take() {
+            log_lines(stdout);
+        }
+        let status = child.wait()?;
+        report_stderr(stderr, status);
+        return Ok(status);
+    }
+
+    fn pipe(
+        &self,
+        cmd_args: &[String],
+        pipe_to: &[String],
+        streamed: &AtomicU64,
+        bandwidth_limit: Option<u32>,
+    ) -> io::Result<ExitStatus> {
+        let mut producer = build_cmd(cmd_args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let consumer = build_cmd(pipe_to)
+            .stdin(Stdio::piped())
+            .stdout(stdout_target())
+            .stderr(Stdio::piped())
+            .spawn();
+        // The producer is already running and would be left behind
+        let mut consumer = match consumer {
+            Ok(consumer) => consumer,
+            Err(err) => {
+                let _ = producer.kill();
+                let _ = producer.wait();
+                return Err(err);
+            }
+        };
+        let producer_stderr = watch_stderr(&mut producer);
+        let consumer_stderr = watch_stderr(&mut consumer);
+
+        let source = producer.stdout.take().expect("Piped stdout required");
+        let sink = consumer.stdin.take().expect("Piped stdin required");
+        // The sink is dropped when pumping ends, closing the consumer's stdin
+        let pumped = thread::scope(|scope| {
+            let pump_thread = scope.spawn(move || pump(source, sink, streamed, bandwidth_limit));
+            if let Some(stdout) = consumer.stdout.take() {
+                log_lines(stdout);
+            }
+            pump_thread.join().expect("Pipe thread panicked")
+        });
+
+        let producer_status = producer.wait()?;
+        let consumer_status = consumer.wait()?;
+        report_stderr(producer_stderr, producer_status);
+        report_stderr(consumer_stderr, consumer_status);
+        if !producer_status.success() {
+            return Ok(producer_status);
+        }
+        if consumer_status.success() {
+            pumped?;
+        }
+        return Ok(consumer_status);
+    }
+}
+
+// Records every command instead of running it. Commands succeed with no output unless one of
+// their arguments was given an exit code or output, a pipe is recorded as both of its commands.
+#[derive(Default)]
+pub struct RecordingRunner {
+    commands: Mutex<Vec<Vec<String>>>,
+    failing: Vec<(String, i32)>,
+    responses: Vec<(String, String)>,
+}
+
+impl RecordingRunner {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    // Commands with this argument exit with 1
+    pub fn fail(self, arg: &str) -> Self {
+        return self.fail_with(arg, 1);
+    }
+
+    pub fn fail_with(mut self, arg: &str, code: i32) -> Self {
+        self.failing.push((arg.to_string(), code));
+        return self;
+    }
+
+    // Commands with this argument print stdout
+    pub fn respond(mut self, arg: &str, stdout: &str) -> Self {
+        self.responses.push((arg.to_string(), stdout.to_string()));
+        return self;
+    }
+
+    pub fn commands(&self) -> Vec<Vec<String>> {
+        return self
+            .commands
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone();
+    }
+
+    fn record(&self, cmd_args: &[String]) -> ExitStatus {
+        self.commands
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(cmd_args.to_vec());
+        let code = self
+            .failing
+            .iter()
+            .find(|(failing_arg, _)| cmd_args.contains(failing_arg))
+            .map(|(_, code)| *code)
+            .unwrap_or(0);
+        // Wait statuses keep the exit code in the second byte
+        return ExitStatus::from_raw(code << 8);
+    }
+}
+
+impl CommandRunner for RecordingRunner {
+    fn output(&self, cmd_args: &[String]) -> io::Result<Output> {
+        let stdout = self
+            .responses
+            .iter()
+            .find(|(arg, _)| cmd_args.contains(arg))
+            .map(|(_, stdout)| stdout.as_bytes().to_vec())
+            .unwrap_or_default();
+        return Ok(Output {
+            status: self.record(cmd_args),
+            stdout,
+            stderr: Vec::new(),
+        });
+    }
+
+    fn run(&self, cmd_args: &[String]) -> io::Result<ExitStatus> {
+        return Ok(self.record(cmd_args));
+    }
+
+    fn pipe(
+        &self,
+        cmd_args: &[String],
+        pipe_to: &[String],
+        _: &AtomicU64,
+        _: Option<u32>,
+    ) -> io::Result<ExitStatus> {
+        let producer_status = self.record(cmd_args);
+        let consumer_status = self.record(pipe_to);
+        if !producer_status.success() {
+            return Ok(producer_status);
+        }
+        return Ok(consumer_status);
+    }
+}
+
+fn build_cmd(cmd_args: &[String]) -> Command {
+    let first_arg = cmd_args.first().expect("First argument required");
+    let mut cmd = Command::new(first_arg);
+    for cmd_arg in &cmd_args[1..] {
+        cmd.arg(cmd_arg);
+    }
+    return cmd;
+}
+
+fn stdout_target() -> Stdio {
+    if logger::streams_subprocess() {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    }
+}
+
+fn pump(
+    mut source: impl Read,
+    mut sink: impl Write,
+    streamed: &AtomicU64,
+    bandwidth_limit: Option<u32>,
+) -> io::Result<()> {
+    let mut buffer = vec![0u8; 64 * 1024];
+    let start = Instant::now();
+    let mut sent = 0;
+    loop {
+        let read = source.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        sink.write_all(&buffer[..read])?;
+        streamed.fetch_add(read as u64, Ordering::Relaxed);
+        sent += read as u64;
+        if let Some(bandwidth_limit) = bandwidth_limit {
+            throttle::pace(sent, bandwidth_limit, start);
+        }
+    }
+    return sink.flush();
+}
+
+fn watch_stderr(child: &mut Child) -> JoinHandle<Vec<String>> {
+    let stderr = child.stderr.take().expect("Piped stderr required");
+    return thread::spawn(move || {
+        let mut lines = Vec::new();
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            debug!(target: SUBPROCESS_TARGET, "{}", line);
+            lines.push(line);
+        }
+        lines
+    });
+}
+
+// Failures explain themselves on the console even when subprocess output is hidden
+fn report_stderr(stderr: JoinHandle<Vec<String>>, status: ExitStatus) -> () {
+    let lines = stderr.join().unwrap_or_default();
+    if !status.success() && logger::console_level() < LevelFilter::Debug {
+        for line in lines {
+            error!("{}", line);
+        }
+    }
+}
+
+fn log_lines(stream: impl Read) -> () {
+    for line in BufReader::new(stream).lines().map_while(Result::ok) {
+        debug!(target: SUBPROCESS_TARGET, "{}", line);
+    }
+}
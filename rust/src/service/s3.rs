@@ -0,0 +1,272 @@
+use crate::model::compression::Compression;
+use crate::model::s3::S3Bucket;
+use crate::service::core::{prioritize, PullSteps, Step, SyncContext, Total};
+use crate::service::progress::{self, Measure, Probe};
+use crate::service::runner;
+use crate::service::tar::{session_name, tar_directory, untar_directory};
+use log::info;
+use std::fs;
+use std::path::Path;
+
+// ETags depend on how the upload was split into parts, so the hash is kept in metadata
+const HASH_METADATA: &str = "dsync-sha256";
+// Exit code of the upload script when the object already holds the archive
+const UNCHANGED_CODE: i32 = 99;
+
+const UPLOAD_SCRIPT: &str = r#"file=$1 bucket=$2 key=$3 name=$4
+shift 4
+hash=$(sha256sum < "$file" | cut -c1-64)
+current=$("$@" s3api head-object --bucket "$bucket" --key "$key" --query "Metadata.\"$name\"" --output text 2>/dev/null)
+if [ "$current" = "$hash" ]; then
+    echo "s3://$bucket/$key is unchanged, sha256 $hash"
+    exit 99
+fi
+exec "$@" s3 cp --no-progress --metadata "$name=$hash" "$file" "s3://$bucket/$key""#;
+
+fn aws_args(bucket: &S3Bucket) -> Vec<String> {
+    let mut aws_args: Vec<String> = Vec::new();
+    aws_args.push("aws".to_string());
+    if let Some(endpoint) = &bucket.endpoint {
+        aws_args.push("--endpoint-url".to_string());
+        aws_args.push(endpoint.clone());
+    }
+    if let Some(region) = &bucket.region {
+        aws_args.push("--region".to_string());
+        aws_args.push(region.clone());
+    }
+    if let Some(profile) = &bucket.profile {
+        aws_args.push("--profile".to_string());
+        aws_args.push(profile.clone());
+    }
+    return aws_args;
+}
+
+pub fn url(bucket: &S3Bucket, key: &str) -> String {
+    return format!("s3://{}/{}", bucket.bucket, key);
+}
+
+pub fn object_key(path: &str, compression: &Compression) -> String {
+    return format!("{}.{}", path.trim_matches('/'), compression.extension());
+}
+
+pub fn list_args(bucket: &S3Bucket, path: &str) -> Vec<String> {
+    let mut list_args = aws_args(bucket);
+    list_args.push("s3".to_string());
+    list_args.push("ls".to_string());
+    list_args.push("--recursive".to_string());
+    list_args.push(url(bucket, path.trim_start_matches('/')));
+    return list_args;
+}
+
+pub fn head_args(bucket: &S3Bucket, key: &str) -> Vec<String> {
+    let mut head_args = aws_args(bucket);
+    head_args.push("s3api".to_string());
+    head_args.push("head-object".to_string());
+    head_args.push("--bucket".to_string());
+    head_args.push(bucket.bucket.clone());
+    head_args.push("--key".to_string());
+    head_args.push(key.to_string());
+    head_args.push("--query".to_string());
+    head_args.push("[ETag,ContentLength]".to_string());
+    head_args.push("--output".to_string());
+    head_args.push("text".to_string());
+    return head_args;
+}
+
+// "\"<etag>\"\t<size>" as printed by head_args
+pub fn parse_head(head_output: &str) -> Option<(String, u64)> {
+    let (etag, size) = head_output.trim().split_once('\t')?;
+    let size = size.trim().parse::<u64>().ok()?;
+    return Some((etag.trim_matches('"').to_string(), size));
+}
+
+pub fn upload_args(bucket: &S3Bucket, from_path: &str, key: &str) -> Vec<String> {
+    let mut upload_args: Vec<String> = Vec::new();
+    upload_args.push("sh".to_string());
+    upload_args.push("-c".to_string());
+    upload_args.push(UPLOAD_SCRIPT.to_string());
+    upload_args.push("sh".to_string());
+    upload_args.push(from_path.to_string());
+    upload_args.push(bucket.bucket.clone());
+    upload_args.push(key.to_string());
+    upload_args.push(HASH_METADATA.to_string());
+    upload_args.extend(aws_args(bucket));
+    return upload_args;
+}
+
+pub fn download_args(bucket: &S3Bucket, key: &str, to_path: &str) -> Vec<String> {
+    let mut download_args = aws_args(bucket);
+    download_args.push("s3".to_string());
+    download_args.push("cp".to_string());
+    download_args.push("--no-progress".to_string());
+    download_args.push(url(bucket, key));
+    download_args.push(to_path.to_string());
+    return download_args;
+}
+
+pub fn record_etag_args(etag: &str, etag_path: &str) -> Vec<String> {
+    let mut record_args: Vec<String> = Vec::new();
+    record_args.push("sh".to_string());
+    record_args.push("-c".to_string());
+    record_args.push("printf '%s\\n' \"$1\" > \"$2\"".to_string());
+    record_args.push("sh".to_string());
+    record_args.push(etag.to_string());
+    record_args.push(etag_path.to_string());
+    return record_args;
+}
+
+// Key, ETag and size of the archive object a path is pulled from
+fn head_object(
+    bucket: &S3Bucket,
+    path: &str,
+    compression: &Compression,
+) -> Result<(String, String, u64), String> {
+    let key = object_key(path, compression);
+    let head_output = runner::get()
+        .output(&head_args(bucket, &key))
+        .map_err(|err| format!("Failed to Check Folder: {}", err))?;
+    if !head_output.status.success() {
+        return Err("Error: From Folder Does Not Exist".to_string());
+    }
+    let (etag, size) = parse_head(&String::from_utf8_lossy(&head_output.stdout))
+        .ok_or(format!("Unexpected head-object output for {}", key))?;
+    return Ok((key, etag, size));
+}
+
+fn session(context: &SyncContext) -> String {
+    return session_name(
+        context.from_folder,
+        &context.from_base,
+        context.to_folder,
+        &context.to_base,
+        &context.from_members,
+    );
+}
+
+// Archives into the local work folder and uploads the archive
+pub fn push_steps(bucket: &S3Bucket, context: &SyncContext) -> Result<Vec<Step>, String> {
+    let work_folder = context
+        .work_folder
+        .as_ref()
+        .ok_or("A work folder is required for s3 folders".to_string())?;
+    let (tar_name, _, create_tar_args, seal_tar_args, delete_from_tar_args) = tar_directory(
+        context.from_base.clone(),
+        &context.from_members,
+        work_folder,
+        &session(context),
+        &context.compression,
+        &context.exclude,
+    );
+    let tar_path = format!("{}/{}", work_folder.path, tar_name);
+    let key = object_key(&context.to_path, &context.compression);
+    let part_size_args = progress::probe_args(Measure::File, vec![format!("{}.part", tar_path)]);
+    return Ok(vec![
+        Step::new(
+            "create_tar",
+            prioritize(context, create_tar_args),
+            "Failed to Create Tar",
+        )
+        .with_progress(
+            "Archive",
+            Probe::Command(part_size_args, Measure::File),
+            Total::Source,
+        ),
+        Step::new("seal_tar", seal_tar_args, "Failed to Seal Tar"),
+        Step::new(
+            "upload",
+            upload_args(bucket, &tar_path, &key),
+            "Failed to Upload Archive",
+        )
+        .with_unchanged_code(UNCHANGED_CODE),
+        Step::new(
+            "delete_from_tar",
+            delete_from_tar_args,
+            "Failed to Delete From Tar",
+        ),
+    ]);
+}
+
+// Downloads the archive into the local work folder and extracts it like a staged transfer,
+// unless it is unchanged since the last pull
+pub fn pull_steps(bucket: &S3Bucket, context: &SyncContext) -> Result<PullSteps, String> {
+    let compression = &context.compression;
+    let (key, etag, size) = head_object(bucket, &context.from_path, compression)?;
+    let work_folder = context
+        .work_folder
+        .as_ref()
+        .ok_or("A work folder is required for s3 folders".to_string())?;
+    let session = session(context);
+    let etag_path = format!("{}/{}.etag", work_folder.path, session);
+    let pulled_etag = fs::read_to_string(&etag_path).unwrap_or_default();
+    if !context.fresh && pulled_etag.trim() == etag && Path::new(&context.to_path).exists() {
+        info!(
+            "{} is unchanged since the last pull, ETag {}",
+            url(bucket, &key),
+            etag
+        );
+        return Ok((Vec::new(), 0, size));
+    }
+
+    let tar_name = format!("{}.{}", session, compression.extension());
+    let tar_path = format!("{}/{}", work_folder.path, tar_name);
+    let (
+        verify_tar_args,
+        make_path_to_target_folder_args,
+        delete_target_folder_args,
+        untar_folder_args,
+        delete_to_tar_args,
+    ) = untar_directory(
+        context.to_base.clone(),
+        &context.to_members,
+        work_folder,
+        tar_name,
+        compression,
+    );
+    let tar_size_args = progress::probe_args(Measure::File, vec![tar_path.clone()]);
+    let tree_size_args = progress::probe_args(Measure::Tree, vec![context.to_path.clone()]);
+    let steps = vec![
+        Step::new(
+            "download",
+            download_args(bucket, &key, &tar_path),
+            "Failed to Download Archive",
+        )
+        .with_progress(
+            "Transfer",
+            Probe::Command(tar_size_args, Measure::File),
+            Total::Source,
+        )
+        .with_cleanup(context.to.remove(&[tar_path])?),
+        Step::new(
+            "make_target_dirs",
+            make_path_to_target_folder_args,
+            "Failed to Make Target Directories",
+        ),
+        Step::new("verify_tar", verify_tar_args, "Failed to Verify Tar"),
+        Step::new(
+            "delete_target",
+            delete_target_folder_args,
+            "Failed to Delete Target Folder",
+        ),
+        Step::new(
+            "untar",
+            prioritize(context, untar_folder_args),
+            "Failed to Untar Archive",
+        )
+        .with_progress(
+            "Extract",
+            Probe::Command(tree_size_args, Measure::Tree),
+            Total::Source,
+        ),
+        Step::new(
+            "delete_to_tar",
+            delete_to_tar_args,
+            "Failed to Delete To Tar",
+        ),
+        Step::new(
+            "record_etag",
+            record_etag_args(&etag, &etag_path),
+            "Failed to Record ETag",
+        ),
+    ];
+    return Ok((steps, 0, size));
+}
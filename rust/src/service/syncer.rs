@@ -0,0 +1,167 @@
+use crate::model::config::Config;
+use crate::model::folder::Folder;
+use crate::model::link::Link;
+use crate::model::options::SyncOptions;
+use crate::model::plan::PlannedSync;
+use crate::model::report::SyncReport;
+use crate::model::schedule::Direction;
+use crate::service::bisync;
+use crate::service::core;
+use crate::service::link;
+use log::warn;
+
+// A link with the relative paths it syncs
+type ResolvedLink<'a> = (&'a Link, Vec<Option<String>>);
+
+// Pushes, pulls and syncs against a config, returning plans and reports instead of printing
+// them. Without `force` in the options every run asks for confirmation on stdin.
+pub struct Syncer<'a> {
+    config: &'a Config,
+    options: SyncOptions,
+}
+
+impl<'a> Syncer<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        Self {
+            config,
+            options: SyncOptions::default(),
+        }
+    }
+
+    // Layered under each link's own settings, like the command line flags
+    pub fn options(mut self, options: SyncOptions) -> Self {
+        self.options = options;
+        return self;
+    }
+
+    pub fn plan_link(
+        &self,
+        name: &str,
+        direction: Direction,
+        relative_path: &Option<String>,
+    ) -> Vec<Result<PlannedSync, String>> {
+        let (link, paths) = match self.resolve(name, relative_path) {
+            Ok(Some(resolved)) => resolved,
+            Ok(None) => return Vec::new(),
+            Err(err) => return vec![Err(err)],
+        };
+        let options = link::options(link, &self.options);
+        return match direction {
+            Direction::Push => self.plan(&link.local, &link.target, &paths, &options),
+            Direction::Pull => self.plan(&link.target, &link.local, &paths, &options),
+            Direction::Sync => vec![Err(
+                "Two way syncs are only planned once both sides are scanned".to_string(),
+            )],
+        };
+    }
+
+    pub fn run_link(
+        &self,
+        name: &str,
+        direction: Direction,
+        relative_path: &Option<String>,
+    ) -> Vec<Result<SyncReport, String>> {
+        let (link, paths) = match self.resolve(name, relative_path) {
+            Ok(Some(resolved)) => resolved,
+            Ok(None) => return Vec::new(),
+            Err(err) => return vec![Err(err)],
+        };
+        let options = link::options(link, &self.options);
+        return match direction {
+            Direction::Push => self.run(&link.local, &link.target, &paths, &options),
+            Direction::Pull => self.run(&link.target, &link.local, &paths, &options),
+            // Every root is scanned for changes, even a partial one
+            Direction::Sync => bisync::sync(
+                link,
+                &self.config.work_folder,
+                &self.config.ssh_servers,
+                relative_path,
+                &options,
+            ),
+        };
+    }
+
+    // Folder to folder, with the options as given
+    pub fn plan_folders(
+        &self,
+        from_folder: &Folder,
+        to_folder: &Folder,
+        relative_path: &Option<String>,
+    ) -> Vec<Result<PlannedSync, String>> {
+        return self.plan(
+            from_folder,
+            to_folder,
+            std::slice::from_ref(relative_path),
+            &self.options,
+        );
+    }
+
+    pub fn run_folders(
+        &self,
+        from_folder: &Folder,
+        to_folder: &Folder,
+        relative_path: &Option<String>,
+    ) -> Vec<Result<SyncReport, String>> {
+        return self.run(
+            from_folder,
+            to_folder,
+            std::slice::from_ref(relative_path),
+            &self.options,
+        );
+    }
+
+    fn plan(
+        &self,
+        from_folder: &Folder,
+        to_folder: &Folder,
+        relative_paths: &[Option<String>],
+        options: &SyncOptions,
+    ) -> Vec<Result<PlannedSync, String>> {
+        return core::plan_only(
+            from_folder,
+            to_folder,
+            &self.config.work_folder,
+            &self.config.ssh_servers,
+            relative_paths,
+            options,
+        );
+    }
+
+    fn run(
+        &self,
+        from_folder: &Folder,
+        to_folder: &Folder,
+        relative_paths: &[Option<String>],
+        options: &SyncOptions,
+    ) -> Vec<Result<SyncReport, String>> {
+        return core::sync(
+            from_folder,
+            to_folder,
+            &self.config.work_folder,
+            &self.config.ssh_servers,
+            relative_paths,
+            options,
+        );
+    }
+
+    // The link's paths win over a relative path, a partial only link needs one of them
+    fn resolve(
+        &self,
+        name: &str,
+        relative_path: &Option<String>,
+    ) -> Result<Option<ResolvedLink<'a>>, String> {
+        let link = self
+            .config
+            .links
+            .get(name)
+            .ok_or(format!("Error locating link: {}", name))?;
+        if !link.paths.is_empty() {
+            return Ok(Some((link, link.paths.iter().cloned().map(Some).collect())));
+        }
+        if link.partial_only && !Option::is_some(relative_path) {
+            warn!("{} is partial only, ending task", link.name);
+            return Ok(None);
+        }
+        return Ok(Some((link, vec![relative_path.clone()])));
+    }
+}
@@ -1,31 +1,102 @@
+use crate::model::compression::Compression;
 use crate::model::folder::Folder;
+use crate::service::exclude;
 use rand::distributions::Alphanumeric;
 use rand::Rng;
 
+// Five commands of an archive transfer, in the order they run
+pub type TarCommands = (
+    Vec<String>,
+    Vec<String>,
+    Vec<String>,
+    Vec<String>,
+    Vec<String>,
+);
+
+// Stable across runs, so an interrupted sync of the same paths finds its archives again
+pub fn session_name(
+    from_folder: &Folder,
+    from_base: &str,
+    to_folder: &Folder,
+    to_base: &str,
+    members: &[String],
+) -> String {
+    let session = format!(
+        "{}:{}>{}:{}/{}",
+        from_folder.ssh_key.clone().unwrap_or_default(),
+        from_base,
+        to_folder.ssh_key.clone().unwrap_or_default(),
+        to_base,
+        members.join("\n")
+    );
+    return format!("dsync-{:016x}", fnv1a(&session));
+}
+
+// FNV-1a, the std hasher is not guaranteed to be stable between releases
+pub fn fnv1a(text: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in text.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    return hash;
+}
+
+// Size, mtime and path of every file under the paths, summed up by cksum
+const FINGERPRINT_SCRIPT: &str = r#"record=$1
+shift
+find "$@" -type f -exec stat -c '%s %Y %n' {} + | LC_ALL=C sort | cksum > "$record""#;
+
+// Writes a fingerprint of the paths to record_path, /dev/stdout prints it instead
+pub fn fingerprint_args(paths: &[String], record_path: &str) -> Vec<String> {
+    let mut fingerprint_args: Vec<String> = Vec::new();
+    fingerprint_args.push("sh".to_string());
+    fingerprint_args.push("-c".to_string());
+    fingerprint_args.push(FINGERPRINT_SCRIPT.to_string());
+    fingerprint_args.push("sh".to_string());
+    fingerprint_args.push(record_path.to_string());
+    fingerprint_args.extend(paths.iter().cloned());
+    return fingerprint_args;
+}
+
+// Archives each member of base_path, the archive is written to a .part file and only renamed
+// once tar succeeded
 pub fn tar_directory(
-    target_path: String,
+    base_path: String,
+    members: &[String],
     work_folder: &Folder,
-) -> (String, Vec<String>, Vec<String>, Vec<String>) {
-    let random_name: String = rand::thread_rng()
-        .sample_iter(&Alphanumeric)
-        .take(7)
-        .map(char::from)
-        .collect();
-    let tar_name = format!("{}.tar.gz", random_name);
+    session: &str,
+    compression: &Compression,
+    exclude: &[String],
+) -> (String, Vec<String>, Vec<String>, Vec<String>, Vec<String>) {
+    let tar_name = format!("{}.{}", session, compression.extension());
     let tar_path = format!("{}/{}", work_folder.path, &tar_name);
+    let part_path = format!("{}.part", tar_path);
 
     let mut tar_source_exists_args: Vec<String> = Vec::new();
     tar_source_exists_args.push("ls".to_string());
-    tar_source_exists_args.push(target_path.clone());
+    for member in members {
+        tar_source_exists_args.push(format!("{}/{}", base_path, member));
+    }
 
-    let target_path_split = split_path(target_path.clone());
     let mut create_tar_args: Vec<String> = Vec::new();
     create_tar_args.push("tar".to_string());
+    if let Some(program) = compression.program(true) {
+        create_tar_args.push(format!("--use-compress-program={}", program));
+    }
+    create_tar_args.extend(exclude::tar_args(exclude));
     create_tar_args.push("-cf".to_string());
-    create_tar_args.push(tar_path.clone());
+    create_tar_args.push(part_path.clone());
     create_tar_args.push("-C".to_string());
-    create_tar_args.push(target_path_split.0);
-    create_tar_args.push(target_path_split.1);
+    create_tar_args.push(base_path);
+    for member in members {
+        create_tar_args.push(member.clone());
+    }
+
+    let mut seal_tar_args: Vec<String> = Vec::new();
+    seal_tar_args.push("mv".to_string());
+    seal_tar_args.push(part_path);
+    seal_tar_args.push(tar_path.clone());
 
     let mut delete_tar_args: Vec<String> = Vec::new();
     delete_tar_args.push("rm".to_string());
@@ -34,21 +105,19 @@ pub fn tar_directory(
         tar_name,
         tar_source_exists_args,
         create_tar_args,
+        seal_tar_args,
         delete_tar_args,
     )
 }
 
+// Replaces only the members of base_path, everything else under it is left alone
 pub fn untar_directory(
-    target_path: String,
+    base_path: String,
+    members: &[String],
     work_folder: &Folder,
     tar_name: String,
-) -> (
-    Vec<String>,
-    Vec<String>,
-    Vec<String>,
-    Vec<String>,
-    Vec<String>,
-) {
+    compression: &Compression,
+) -> TarCommands {
     let mut verify_tar: Vec<String> = Vec::new();
     let mut make_path_to_target_folder: Vec<String> = Vec::new();
     let mut delete_target_folder: Vec<String> = Vec::new();
@@ -61,18 +130,21 @@ pub fn untar_directory(
 
     make_path_to_target_folder.push("mkdir".to_string());
     make_path_to_target_folder.push("-p".to_string());
-    make_path_to_target_folder.push(target_path.clone());
-
     delete_target_folder.push("rm".to_string());
     delete_target_folder.push("-rf".to_string());
-    delete_target_folder.push(target_path.clone());
+    for member in members {
+        make_path_to_target_folder.push(format!("{}/{}", base_path, member));
+        delete_target_folder.push(format!("{}/{}", base_path, member));
+    }
 
-    let target_path_split = split_path(target_path.clone());
     untar_folder.push("tar".to_string());
+    if let Some(program) = compression.program(false) {
+        untar_folder.push(format!("--use-compress-program={}", program));
+    }
     untar_folder.push("-xf".to_string());
     untar_folder.push(tar_path.clone());
     untar_folder.push("-C".to_string());
-    untar_folder.push(target_path_split.0);
+    untar_folder.push(base_path);
 
     delete_tar.push("rm".to_string());
     delete_tar.push(tar_path.clone());
@@ -86,9 +158,97 @@ pub fn untar_directory(
     )
 }
 
-fn split_path(path: String) -> (String, String) {
+// Same layout as tar_directory/untar_directory but reading and writing the archive on stdio
+pub fn stream_directory(
+    from_path: String,
+    to_path: String,
+    staging_dir: &Option<String>,
+    compression: &Compression,
+    exclude: &[String],
+) -> TarCommands {
+    let mut tar_source_exists_args: Vec<String> = Vec::new();
+    let mut create_tar_args: Vec<String> = Vec::new();
+    let mut make_path_to_target_folder: Vec<String> = Vec::new();
+    let mut delete_target_folder: Vec<String> = Vec::new();
+    let mut untar_folder: Vec<String> = Vec::new();
+
+    tar_source_exists_args.push("ls".to_string());
+    tar_source_exists_args.push(from_path.clone());
+
+    let from_path_split = split_path(from_path.clone());
+    create_tar_args.push("tar".to_string());
+    if let Some(program) = compression.program(true) {
+        create_tar_args.push(format!("--use-compress-program={}", program));
+    }
+    create_tar_args.extend(exclude::tar_args(exclude));
+    create_tar_args.push("-cf".to_string());
+    create_tar_args.push("-".to_string());
+    create_tar_args.push("-C".to_string());
+    create_tar_args.push(from_path_split.0);
+    create_tar_args.push(from_path_split.1);
+
+    make_path_to_target_folder.push("mkdir".to_string());
+    make_path_to_target_folder.push("-p".to_string());
+    make_path_to_target_folder.push(to_path.clone());
+
+    delete_target_folder.push("rm".to_string());
+    delete_target_folder.push("-rf".to_string());
+    delete_target_folder.push(to_path.clone());
+
+    let to_path_split = split_path(to_path.clone());
+    untar_folder.push("tar".to_string());
+    if let Some(program) = compression.program(false) {
+        untar_folder.push(format!("--use-compress-program={}", program));
+    }
+    untar_folder.push("-xf".to_string());
+    untar_folder.push("-".to_string());
+    untar_folder.push("-C".to_string());
+    untar_folder.push(staging_dir.clone().unwrap_or(to_path_split.0));
+
+    (
+        tar_source_exists_args,
+        create_tar_args,
+        make_path_to_target_folder,
+        delete_target_folder,
+        untar_folder,
+    )
+}
+
+// A hidden sibling of the target that a stream is extracted into until it has been verified
+pub fn stage_directory(to_path: String) -> (String, Vec<String>, Vec<String>, Vec<String>) {
+    let random_name: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(7)
+        .map(char::from)
+        .collect();
+    let to_path_split = split_path(to_path.clone());
+    let staging_dir = format!("{}/.dsync-{}", to_path_split.0, random_name);
+
+    let mut make_staging_dir: Vec<String> = Vec::new();
+    make_staging_dir.push("mkdir".to_string());
+    make_staging_dir.push("-p".to_string());
+    make_staging_dir.push(staging_dir.clone());
+
+    let mut move_into_place: Vec<String> = Vec::new();
+    move_into_place.push("mv".to_string());
+    move_into_place.push(format!("{}/{}", staging_dir, to_path_split.1));
+    move_into_place.push(to_path.clone());
+
+    let mut delete_staging_dir: Vec<String> = Vec::new();
+    delete_staging_dir.push("rmdir".to_string());
+    delete_staging_dir.push(staging_dir.clone());
+
+    (
+        staging_dir,
+        make_staging_dir,
+        move_into_place,
+        delete_staging_dir,
+    )
+}
+
+pub fn split_path(path: String) -> (String, String) {
     let mut split_path: Vec<&str> = path.split("/").collect();
-    let target = if let Some(..) = split_path.last() {
+    let target = if split_path.last().is_some() {
         split_path.pop().expect("One argument required").to_string()
     } else {
         String::new()
@@ -96,7 +256,7 @@ fn split_path(path: String) -> (String, String) {
 
     let mut base_path = String::new();
     for arg in split_path {
-        if arg.trim().len() > 0 {
+        if !arg.trim().is_empty() {
             base_path = format!("{}/{}", base_path, arg);
         }
     }
@@ -0,0 +1,52 @@
+use crate::model::{folder::Folder, ssh::SshServer};
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// The command line wins over the server, unlimited by default
+pub fn resolve_bandwidth_limit(
+    cli_bandwidth_limit: &Option<u32>,
+    from_folder: &Folder,
+    to_folder: &Folder,
+    ssh_servers: &HashMap<String, SshServer>,
+) -> Option<u32> {
+    let ssh_server = crate::service::ssh::get_remote(from_folder, to_folder, ssh_servers);
+    return cli_bandwidth_limit
+        .or_else(|| ssh_server.and_then(|ssh_server| ssh_server.bandwidth_limit));
+}
+
+// The link or command line wins over the server, normal priority by default
+pub fn resolve_low_priority(
+    link_low_priority: &Option<bool>,
+    from_folder: &Folder,
+    to_folder: &Folder,
+    ssh_servers: &HashMap<String, SshServer>,
+) -> bool {
+    let ssh_server = crate::service::ssh::get_remote(from_folder, to_folder, ssh_servers);
+    return link_low_priority
+        .or_else(|| ssh_server.and_then(|ssh_server| ssh_server.low_priority))
+        .unwrap_or(false);
+}
+
+// Lowest CPU priority and the idle I/O class, applied before any ssh prefix
+pub fn lower_priority(cmd_args: Vec<String>) -> Vec<String> {
+    let mut priority_args: Vec<String> = Vec::new();
+    priority_args.push("nice".to_string());
+    priority_args.push("-n".to_string());
+    priority_args.push("19".to_string());
+    priority_args.push("ionice".to_string());
+    priority_args.push("-c".to_string());
+    priority_args.push("3".to_string());
+    priority_args.extend(cmd_args);
+    return priority_args;
+}
+
+// Sleeps until `sent` bytes fit within the limit, given in Kbit/s like scp -l
+pub fn pace(sent: u64, bandwidth_limit: u32, start: Instant) -> () {
+    let bytes_per_second = (bandwidth_limit as u64 * 1000 / 8).max(1);
+    let due = Duration::from_secs_f64(sent as f64 / bytes_per_second as f64);
+    let elapsed = start.elapsed();
+    if due > elapsed {
+        thread::sleep(due - elapsed);
+    }
+}
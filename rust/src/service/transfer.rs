@@ -0,0 +1,15 @@
+use crate::model::{folder::Folder, ssh::SshServer, transfer::TransferMode};
+use std::collections::HashMap;
+
+// The link setting wins over the server, staged transfers are the default
+pub fn resolve(
+    link_transfer: &Option<TransferMode>,
+    from_folder: &Folder,
+    to_folder: &Folder,
+    ssh_servers: &HashMap<String, SshServer>,
+) -> TransferMode {
+    let ssh_server = crate::service::ssh::get_remote(from_folder, to_folder, ssh_servers);
+    return link_transfer
+        .or_else(|| ssh_server.and_then(|ssh_server| ssh_server.transfer))
+        .unwrap_or(TransferMode::Staged);
+}
@@ -0,0 +1,202 @@
+use crate::model::folder::{Folder, FolderType};
+use crate::model::link::Link;
+use crate::model::options::SyncOptions;
+use crate::model::ssh::SshServer;
+use crate::service::core::{self, build_path};
+use crate::service::exclude;
+use crate::service::output;
+use log::{debug, info, warn};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+// Pushes the changed part of each root until the watcher goes away
+pub fn watch(
+    link: &Link,
+    work_folder: &Option<Folder>,
+    ssh_servers: &HashMap<String, SshServer>,
+    relative_path: &Option<String>,
+    options: &SyncOptions,
+    debounce: Duration,
+) -> Result<(), String> {
+    if !matches!(link.local.target, FolderType::Local) {
+        return Err(format!(
+            "Unable to watch {}, only local folders can be watched",
+            link.local.name
+        ));
+    }
+    let roots: Vec<Option<String>> = if relative_path.is_some() || link.paths.is_empty() {
+        vec![relative_path.clone()]
+    } else {
+        link.paths.iter().cloned().map(Some).collect()
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(sender).map_err(|err| format!("Failed to Watch: {}", err))?;
+    for root in roots.iter() {
+        let path = build_path(&link.local, root);
+        watcher
+            .watch(Path::new(&path), RecursiveMode::Recursive)
+            .map_err(|err| format!("Failed to Watch {}: {}", path, err))?;
+        info!("Watching {}", path);
+    }
+
+    // Nobody is there to answer a prompt, or to retry a locked destination
+    let options = SyncOptions {
+        force: true,
+        wait: options.wait.or(Some(true)),
+        ..options.clone()
+    };
+    // Archives staged inside the watched folder would trigger the next push
+    let ignored = work_folder
+        .as_ref()
+        .filter(|folder| matches!(folder.target, FolderType::Local))
+        .map(|folder| folder.path.clone());
+    let collect = |event: notify::Result<Event>, changed: &mut BTreeSet<String>| match event {
+        Ok(event) if matches!(event.kind, EventKind::Access(_)) => (),
+        Ok(event) => {
+            for path in event.paths {
+                if ignored
+                    .as_ref()
+                    .is_some_and(|ignored| path.starts_with(ignored))
+                {
+                    continue;
+                }
+                let Ok(relative) = path.strip_prefix(&link.local.path) else {
+                    continue;
+                };
+                let relative = relative.to_string_lossy().to_string();
+                if relative.is_empty() || exclude::is_excluded(&relative, &options.exclude) {
+                    continue;
+                }
+                changed.insert(relative);
+            }
+        }
+        Err(err) => warn!("Watch error: {}", err),
+    };
+
+    while let Ok(event) = receiver.recv() {
+        let mut changed = BTreeSet::new();
+        collect(event, &mut changed);
+        // Editors and builds write in bursts, wait for them to settle
+        loop {
+            match receiver.recv_timeout(debounce) {
+                Ok(event) => collect(event, &mut changed),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+        if changed.is_empty() {
+            continue;
+        }
+        debug!("Changed: {:?}", changed);
+        let subtrees = subtrees(&roots, &changed);
+        for result in core::sync(
+            &link.local,
+            &link.target,
+            work_folder,
+            ssh_servers,
+            &subtrees,
+            &options,
+        ) {
+            if let Err(err) = result {
+                output::error(&err);
+            }
+        }
+    }
+    return Ok(());
+}
+
+// Deepest directory holding every change under each root, never above the root itself
+fn subtrees(roots: &[Option<String>], changed: &BTreeSet<String>) -> Vec<Option<String>> {
+    let mut subtrees = Vec::new();
+    for root in roots {
+        let root_components: Vec<&str> = match root {
+            Some(root) => root.split('/').filter(|part| !part.is_empty()).collect(),
+            None => Vec::new(),
+        };
+        let mut common: Option<Vec<&str>> = None;
+        for path in changed.iter() {
+            let components: Vec<&str> = path.split('/').collect();
+            if !components.starts_with(&root_components) {
+                continue;
+            }
+            // The parent of a changed file, it may have been deleted
+            let parent = &components[..components.len() - 1];
+            common = Some(match common {
+                None => parent.to_vec(),
+                Some(common) => common
+                    .iter()
+                    .zip(parent)
+                    .take_while(|(left, right)| left == right)
+                    .map(|(left, _)| *left)
+                    .collect(),
+            });
+        }
+        let Some(mut common) = common else {
+            continue;
+        };
+        if common.len() < root_components.len() {
+            common = root_components.clone();
+        }
+        subtrees.push(match common.is_empty() {
+            true => None,
+            false => Some(common.join("/")),
+        });
+    }
+    return subtrees;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changed(paths: &[&str]) -> BTreeSet<String> {
+        return paths.iter().map(|path| path.to_string()).collect();
+    }
+
+    fn root(path: &str) -> Option<String> {
+        return Some(path.to_string());
+    }
+
+    #[test]
+    fn changes_in_one_folder_push_that_folder() {
+        let changed = changed(&["src/net/tcp.rs", "src/net/udp.rs"]);
+        assert_eq!(subtrees(&[None], &changed), vec![root("src/net")]);
+    }
+
+    #[test]
+    fn changes_in_sibling_folders_push_their_common_parent() {
+        let siblings = changed(&["src/net/tcp.rs", "src/fs/file.rs"]);
+        assert_eq!(subtrees(&[None], &siblings), vec![root("src")]);
+        let apart = changed(&["src/net/tcp.rs", "docs/README.md"]);
+        assert_eq!(subtrees(&[None], &apart), vec![None]);
+    }
+
+    #[test]
+    fn a_change_at_the_top_pushes_everything() {
+        let changed = changed(&["Cargo.toml", "src/net/tcp.rs"]);
+        assert_eq!(subtrees(&[None], &changed), vec![None]);
+    }
+
+    #[test]
+    fn never_goes_above_the_root() {
+        let top = changed(&["src/lib.rs", "src"]);
+        assert_eq!(subtrees(&[root("src")], &top), vec![root("src")]);
+        let nested = changed(&["src/net/tcp.rs"]);
+        assert_eq!(subtrees(&[root("src/")], &nested), vec![root("src/net")]);
+    }
+
+    #[test]
+    fn roots_without_changes_are_left_out() {
+        let changed = changed(&["src/net/tcp.rs", "srcs/other.rs"]);
+        assert_eq!(
+            subtrees(&[root("docs"), root("src"), root("srcs")], &changed),
+            vec![root("src/net"), root("srcs")]
+        );
+        assert!(subtrees(&[None], &BTreeSet::new()).is_empty());
+    }
+}
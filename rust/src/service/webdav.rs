@@ -0,0 +1,478 @@
+use crate::model::webdav::{WebDavEntry, WebDavServer};
+use crate::service::core::{PullSteps, Step, SyncContext, Total};
+use crate::service::exclude;
+use crate::service::progress::{self, Measure, Probe};
+use crate::service::runner;
+use crate::service::tar::stage_directory;
+use std::fs;
+
+fn curl_args(server: &WebDavServer) -> Vec<String> {
+    let mut curl_args: Vec<String> = Vec::new();
+    curl_args.push("curl".to_string());
+    curl_args.push("-sS".to_string());
+    match &server.netrc {
+        Some(netrc) => {
+            curl_args.push("--netrc-file".to_string());
+            curl_args.push(netrc.clone());
+        }
+        None => curl_args.push("--netrc-optional".to_string()),
+    }
+    return curl_args;
+}
+
+pub fn url(server: &WebDavServer, path: &str) -> String {
+    return format!(
+        "{}/{}",
+        server.url.trim_end_matches('/'),
+        encode_path(path.trim_start_matches('/'))
+    );
+}
+
+// Collections are addressed with a trailing slash
+fn collection_url(server: &WebDavServer, path: &str) -> String {
+    return format!("{}/", url(server, path).trim_end_matches('/'));
+}
+
+pub fn propfind_args(server: &WebDavServer, path: &str) -> Vec<String> {
+    let mut propfind_args = curl_args(server);
+    propfind_args.push("-f".to_string());
+    propfind_args.push("-X".to_string());
+    propfind_args.push("PROPFIND".to_string());
+    propfind_args.push("-H".to_string());
+    propfind_args.push("Depth: 1".to_string());
+    propfind_args.push(collection_url(server, path));
+    return propfind_args;
+}
+
+// Existing collections are answered with 405
+pub fn mkcol_args(server: &WebDavServer, paths: &[String], existing_ok: bool) -> Vec<String> {
+    let mut mkcol_args = curl_args(server);
+    if !existing_ok {
+        mkcol_args.push("-f".to_string());
+        mkcol_args.push("--fail-early".to_string());
+    }
+    mkcol_args.push("-X".to_string());
+    mkcol_args.push("MKCOL".to_string());
+    for path in paths {
+        mkcol_args.push(collection_url(server, path));
+    }
+    return mkcol_args;
+}
+
+pub fn delete_args(server: &WebDavServer, path: &str) -> Vec<String> {
+    let mut delete_args = curl_args(server);
+    delete_args.push("-X".to_string());
+    delete_args.push("DELETE".to_string());
+    delete_args.push(collection_url(server, path));
+    return delete_args;
+}
+
+pub fn move_args(server: &WebDavServer, from_path: &str, to_path: &str) -> Vec<String> {
+    let mut move_args = curl_args(server);
+    move_args.push("-f".to_string());
+    move_args.push("-X".to_string());
+    move_args.push("MOVE".to_string());
+    move_args.push("-H".to_string());
+    move_args.push(format!("Destination: {}", collection_url(server, to_path)));
+    move_args.push("-H".to_string());
+    move_args.push("Overwrite: T".to_string());
+    move_args.push(collection_url(server, from_path));
+    return move_args;
+}
+
+pub fn upload_args(server: &WebDavServer, files: &[(String, String)]) -> Vec<String> {
+    let mut upload_args = curl_args(server);
+    upload_args.push("-f".to_string());
+    upload_args.push("--fail-early".to_string());
+    for (local_path, path) in files {
+        upload_args.push("-T".to_string());
+        upload_args.push(local_path.clone());
+        upload_args.push(url(server, path));
+    }
+    return upload_args;
+}
+
+pub fn download_args(server: &WebDavServer, files: &[(String, String)]) -> Vec<String> {
+    let mut download_args = curl_args(server);
+    download_args.push("-f".to_string());
+    download_args.push("--fail-early".to_string());
+    for (path, local_path) in files {
+        download_args.push(url(server, path));
+        download_args.push("-o".to_string());
+        download_args.push(local_path.clone());
+    }
+    return download_args;
+}
+
+// Copies file by file into a hidden staging folder next to the destination, which replaces the
+// destination once everything arrived
+pub fn push_steps(server: &WebDavServer, context: &SyncContext) -> Result<Vec<Step>, String> {
+    let (staging_dir, _, _, _) = stage_directory(context.to_path.clone());
+    let staged_path = format!("{}/{}", staging_dir, context.to_members[0]);
+    let mut parents: Vec<String> = Vec::new();
+    let mut ancestor = String::new();
+    for component in context.to_base.split('/').filter(|part| !part.is_empty()) {
+        ancestor = format!("{}/{}", ancestor, component);
+        parents.push(ancestor.clone());
+    }
+    let mut collections = vec![staging_dir.clone(), staged_path.clone()];
+    let mut files: Vec<(String, String)> = Vec::new();
+    for (relative, is_dir) in local_tree(&context.from_path, "")? {
+        if exclude::is_excluded(&relative, &context.exclude) {
+            continue;
+        }
+        match is_dir {
+            true => collections.push(format!("{}/{}", staged_path, relative)),
+            false => files.push((
+                format!("{}/{}", context.from_path, relative),
+                format!("{}/{}", staged_path, relative),
+            )),
+        }
+    }
+    files.sort();
+
+    // Parents may exist already, the staging collections are new
+    let mut steps = Vec::new();
+    if !parents.is_empty() {
+        steps.push(Step::new(
+            "make_parent_dirs",
+            mkcol_args(server, &parents, true),
+            "Failed to Make Parent Directories",
+        ));
+    }
+    for batch in runner::batches(&collections, |path| path.len() * 3) {
+        steps.push(
+            Step::new(
+                "make_target_dirs",
+                mkcol_args(server, batch, false),
+                "Failed to Make Target Directories",
+            )
+            .with_cleanup(delete_args(server, &staging_dir)),
+        );
+    }
+    let file_size = |(local_path, path): &(String, String)| local_path.len() + path.len() * 3;
+    for batch in runner::batches(&files, file_size) {
+        steps.push(
+            Step::new(
+                "upload",
+                upload_args(server, batch),
+                "Failed to Upload Files",
+            )
+            .with_cleanup(delete_args(server, &staging_dir)),
+        );
+    }
+    steps.push(Step::new(
+        "delete_target",
+        delete_args(server, &context.to_path),
+        "Failed to Delete Target Folder",
+    ));
+    steps.push(Step::new(
+        "move_into_place",
+        move_args(server, &staged_path, &context.to_path),
+        "Failed to Move Into Place",
+    ));
+    steps.push(Step::new(
+        "delete_staging_dir",
+        delete_args(server, &staging_dir),
+        "Failed to Delete Staging Directory",
+    ));
+    return Ok(steps);
+}
+
+// Downloads into a staging folder next to the local destination, like a push
+pub fn pull_steps(server: &WebDavServer, context: &SyncContext) -> Result<PullSteps, String> {
+    let entries: Vec<WebDavEntry> = tree(server, &context.from_path)?
+        .ok_or("Error: From Folder Does Not Exist".to_string())?
+        .into_iter()
+        .filter(|entry| !exclude::is_excluded(&entry.path, &context.exclude))
+        .collect();
+    let remote_files = entries.iter().filter(|entry| !entry.is_dir);
+    let (files, bytes) = (
+        remote_files.clone().count() as u64,
+        remote_files.map(|entry| entry.size).sum(),
+    );
+    let (staging_dir, _, move_into_place, delete_staging_dir) =
+        stage_directory(context.to_path.clone());
+    let staged_path = format!("{}/{}", staging_dir, context.to_members[0]);
+    let mut dirs = vec![staged_path.clone()];
+    dirs.extend(
+        entries
+            .iter()
+            .filter(|entry| entry.is_dir)
+            .map(|entry| format!("{}/{}", staged_path, entry.path)),
+    );
+    let downloads: Vec<(String, String)> = entries
+        .iter()
+        .filter(|entry| !entry.is_dir)
+        .map(|entry| {
+            (
+                format!("{}/{}", context.from_path, entry.path),
+                format!("{}/{}", staged_path, entry.path),
+            )
+        })
+        .collect();
+
+    let mut steps = vec![Step::new(
+        "make_staging_dir",
+        context.to.mkdir(&dirs)?,
+        "Failed to Make Staging Directory",
+    )];
+    let file_size = |(path, local_path): &(String, String)| path.len() * 3 + local_path.len();
+    for batch in runner::batches(&downloads, file_size) {
+        let staged_size_args = progress::probe_args(Measure::Tree, vec![staged_path.clone()]);
+        steps.push(
+            Step::new(
+                "download",
+                download_args(server, batch),
+                "Failed to Download Files",
+            )
+            .with_progress(
+                "Transfer",
+                Probe::Command(staged_size_args, Measure::Tree),
+                Total::Source,
+            )
+            .with_cleanup(context.to.remove(std::slice::from_ref(&staging_dir))?),
+        );
+    }
+    steps.push(Step::new(
+        "delete_target",
+        context.to.remove(std::slice::from_ref(&context.to_path))?,
+        "Failed to Delete Target Folder",
+    ));
+    steps.push(Step::new(
+        "move_into_place",
+        move_into_place,
+        "Failed to Move Into Place",
+    ));
+    steps.push(Step::new(
+        "delete_staging_dir",
+        delete_staging_dir,
+        "Failed to Delete Staging Directory",
+    ));
+    return Ok((steps, files, bytes));
+}
+
+// Paths under root and whether each is a directory, symlinks are left out like any other
+// special file
+fn local_tree(root: &str, relative: &str) -> Result<Vec<(String, bool)>, String> {
+    let list_error = |err: std::io::Error| format!("Failed to List Files: {}", err);
+    let mut tree = Vec::new();
+    for dir_entry in fs::read_dir(join(root, relative)).map_err(list_error)? {
+        let dir_entry = dir_entry.map_err(list_error)?;
+        let file_type = dir_entry.file_type().map_err(list_error)?;
+        let path = join(relative, &dir_entry.file_name().to_string_lossy());
+        if file_type.is_dir() {
+            tree.push((path.clone(), true));
+            tree.extend(local_tree(root, &path)?);
+        } else if file_type.is_file() {
+            tree.push((path, false));
+        }
+    }
+    return Ok(tree);
+}
+
+// One PROPFIND per collection, servers often refuse "Depth: infinity"
+pub fn tree(server: &WebDavServer, path: &str) -> Result<Option<Vec<WebDavEntry>>, String> {
+    let root = url_path(&url(server, path));
+    let mut entries = Vec::new();
+    let mut pending = vec![String::new()];
+    while let Some(dir) = pending.pop() {
+        let propfind_args = propfind_args(server, &join(path, &dir));
+        let propfind_output = runner::get()
+            .output(&propfind_args)
+            .map_err(|err| format!("Failed to List WebDAV Folder: {}", err))?;
+        if !propfind_output.status.success() {
+            if dir.is_empty() {
+                return Ok(None);
+            }
+            return Err(format!("Failed to List WebDAV Folder: {}", dir));
+        }
+        for entry in parse_propfind(&String::from_utf8_lossy(&propfind_output.stdout)) {
+            let entry_path = url_path(&entry.path);
+            let Some(relative) = relative_to(&entry_path, &root) else {
+                continue;
+            };
+            let relative = relative.to_string();
+            if relative == dir {
+                continue;
+            }
+            if entry.is_dir {
+                pending.push(relative.clone());
+            }
+            entries.push(WebDavEntry {
+                path: relative,
+                ..entry
+            });
+        }
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    return Ok(Some(entries));
+}
+
+// Directories end in a slash, files are followed by their size
+pub fn listing(propfind_output: &str, path: &str, server: &WebDavServer) -> String {
+    let root = url_path(&url(server, path));
+    return parse_propfind(propfind_output)
+        .iter()
+        .filter_map(|entry| {
+            let entry_path = url_path(&entry.path);
+            let name = relative_to(&entry_path, &root)?;
+            if name.is_empty() {
+                return None;
+            }
+            Some(match entry.is_dir {
+                true => format!("{}/", name),
+                false => format!("{}  {}", name, entry.size),
+            })
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+}
+
+pub fn parse_propfind(propfind_output: &str) -> Vec<WebDavEntry> {
+    return elements(propfind_output, "response")
+        .into_iter()
+        .filter_map(|response| {
+            let href = decode_entities(elements(response, "href").first()?.trim());
+            Some(WebDavEntry {
+                path: href,
+                is_dir: !elements(response, "collection").is_empty(),
+                size: elements(response, "getcontentlength")
+                    .first()
+                    .and_then(|size| size.trim().parse::<u64>().ok())
+                    .unwrap_or(0),
+            })
+        })
+        .collect();
+}
+
+// Elements by local name, whatever namespace prefix they carry
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        let after = &rest[start + 1..];
+        let Some(end) = after.find('>') else {
+            break;
+        };
+        let tag = &after[..end];
+        rest = &after[end + 1..];
+        if tag.starts_with(['/', '?', '!']) {
+            continue;
+        }
+        let tag_name = tag
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default();
+        if tag_name.rsplit(':').next() != Some(name) {
+            continue;
+        }
+        if tag.ends_with('/') {
+            found.push("");
+            continue;
+        }
+        let close = format!("</{}>", tag_name);
+        let Some(close_at) = rest.find(&close) else {
+            break;
+        };
+        found.push(&rest[..close_at]);
+        rest = &rest[close_at + close.len()..];
+    }
+    return found;
+}
+
+fn url_path(url: &str) -> String {
+    let path = match url.split_once("://") {
+        Some((_, rest)) => rest.find('/').map(|at| &rest[at..]).unwrap_or("/"),
+        None => url,
+    };
+    return decode_path(path).trim_end_matches('/').to_string();
+}
+
+fn relative_to<'a>(path: &'a str, root: &str) -> Option<&'a str> {
+    let relative = path.strip_prefix(root)?;
+    if !relative.is_empty() && !relative.starts_with('/') {
+        return None;
+    }
+    return Some(relative.trim_matches('/'));
+}
+
+fn join(path: &str, relative: &str) -> String {
+    if relative.is_empty() {
+        return path.to_string();
+    }
+    return format!("{}/{}", path.trim_end_matches('/'), relative);
+}
+
+fn encode_path(path: &str) -> String {
+    let mut encoded = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    return encoded;
+}
+
+fn decode_path(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = path
+            .get(index + 1..index + 3)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[index], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    return String::from_utf8_lossy(&decoded).to_string();
+}
+
+// The predefined XML entities and character references
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest.find(';').map(|end| &rest[1..end]);
+        let character = match entity {
+            Some("amp") => Some('&'),
+            Some("lt") => Some('<'),
+            Some("gt") => Some('>'),
+            Some("quot") => Some('"'),
+            Some("apos") => Some('\''),
+            Some(entity) => entity.strip_prefix('#').and_then(|code| {
+                match code.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => code.parse::<u32>().ok(),
+                }
+                .and_then(char::from_u32)
+            }),
+            None => None,
+        };
+        match (character, entity) {
+            (Some(character), Some(entity)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    return decoded;
+}
@@ -0,0 +1,196 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+mod common;
+
+use common::{read, run, write};
+use directory_sync::model::archive::ArchiveFormat;
+use directory_sync::service::{archive, core, endpoint};
+use directory_sync::{Config, Direction};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const SUITE: &str = "archive";
+
+// A scratch tree per test, archives are gzip compressed or zip files so zstd is not needed
+fn setup(test: &str) -> PathBuf {
+    return common::setup(SUITE, test, &["project", "restore"], |_| ());
+}
+
+fn config(root: &Path, archive: &str, paths: &str) -> Config {
+    return common::config(format!(
+        r#"
+[folders.project]
+path = "{root}/project"
+target = "local"
+
+[folders.restore]
+path = "{root}/restore"
+target = "local"
+
+[folders.handoff]
+path = "{root}/out/{archive}"
+target = "archive"
+
+[links.handoff]
+local = "project"
+target = "handoff"
+paths = [{paths}]
+partial_only = "false"
+exclude = ["*.log"]
+
+[links.restore]
+local = "restore"
+target = "handoff"
+paths = [{paths}]
+partial_only = "false"
+
+[ssh]
+"#,
+        root = root.display(),
+    ));
+}
+
+// The sorted entry paths of an archive, as the archive module lists them
+fn entries(archive_path: &Path) -> Vec<String> {
+    let format = ArchiveFormat::from_path(archive_path.to_str().unwrap()).unwrap();
+    let entries_args = archive::entries_args(format, archive_path.to_str().unwrap());
+    let output = Command::new(&entries_args[0])
+        .args(&entries_args[1..])
+        .output()
+        .expect("List archive");
+    assert!(output.status.success());
+    let mut entries: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|entry| entry.to_string())
+        .collect();
+    entries.sort();
+    return entries;
+}
+
+fn project(root: &Path) -> () {
+    write(root.join("project/src/main.rs"), "fn main() {}\n");
+    write(root.join("project/src/debug.log"), "left out\n");
+    write(root.join("project/docs/guide/intro.md"), "# Intro\n");
+    write(root.join("project/notes.txt"), "not linked\n");
+}
+
+#[test]
+fn tar_snapshot_holds_every_path() {
+    let root = setup("tar_snapshot_holds_every_path");
+    let config = config(&root, "handoff.tar.gz", r#""src", "docs/guide""#);
+    project(&root);
+
+    let reports = run(&config, "handoff", Direction::Push);
+    assert!(reports[0].is_ok(), "{:?}", reports[0]);
+    assert_eq!(reports.len(), 1);
+    assert_eq!(
+        entries(&root.join("out/handoff.tar.gz")),
+        ["docs/guide/", "docs/guide/intro.md", "src/", "src/main.rs"]
+    );
+    assert!(!root.join("out/handoff.tar.gz.part").exists());
+
+    write(root.join("restore/src/stale.rs"), "replaced\n");
+    write(root.join("restore/keep.txt"), "left alone\n");
+    let reports = run(&config, "restore", Direction::Pull);
+    assert!(reports[0].is_ok(), "{:?}", reports[0]);
+    assert_eq!(read(root.join("restore/src/main.rs")), "fn main() {}\n");
+    assert_eq!(read(root.join("restore/docs/guide/intro.md")), "# Intro\n");
+    assert!(!root.join("restore/src/stale.rs").exists());
+    assert_eq!(read(root.join("restore/keep.txt")), "left alone\n");
+}
+
+#[test]
+fn zip_snapshot_of_whole_folder() {
+    let root = setup("zip_snapshot_of_whole_folder");
+    let whole = config(&root, "handoff.zip", "");
+    project(&root);
+
+    let reports = run(&whole, "handoff", Direction::Push);
+    assert!(reports[0].is_ok(), "{:?}", reports[0]);
+    assert_eq!(
+        entries(&root.join("out/handoff.zip")),
+        [
+            "docs/",
+            "docs/guide/",
+            "docs/guide/intro.md",
+            "notes.txt",
+            "src/",
+            "src/main.rs"
+        ]
+    );
+
+    write(root.join("restore/stale.txt"), "replaced\n");
+    let reports = run(&whole, "restore", Direction::Pull);
+    assert!(reports[0].is_ok(), "{:?}", reports[0]);
+    assert_eq!(read(root.join("restore/notes.txt")), "not linked\n");
+    assert_eq!(read(root.join("restore/docs/guide/intro.md")), "# Intro\n");
+    assert!(!root.join("restore/stale.txt").exists());
+
+    // A part left by an interrupted push is not added to
+    fs::copy(
+        root.join("out/handoff.zip"),
+        root.join("out/handoff.zip.part"),
+    )
+    .unwrap();
+    fs::remove_file(root.join("project/notes.txt")).unwrap();
+    let reports = run(&whole, "handoff", Direction::Push);
+    assert!(reports[0].is_ok(), "{:?}", reports[0]);
+    assert!(!entries(&root.join("out/handoff.zip")).contains(&"notes.txt".to_string()));
+
+    // Pushing only src would drop docs from the archive
+    let partial = config(&root, "handoff.zip", r#""src""#);
+    assert_eq!(
+        run(&partial, "handoff", Direction::Push)[0]
+            .as_ref()
+            .unwrap_err(),
+        &format!(
+            "{}/out/handoff.zip also holds docs, push the whole folder to replace it",
+            root.display()
+        )
+    );
+}
+
+#[test]
+fn missing_archives_and_paths() {
+    let root = setup("missing_archives_and_paths");
+    let linked = config(&root, "handoff.zip", r#""src", "notes.txt""#);
+    assert_eq!(
+        run(&linked, "restore", Direction::Pull)[0]
+            .as_ref()
+            .unwrap_err(),
+        "Error: From Folder Does Not Exist"
+    );
+
+    project(&root);
+    assert!(run(&linked, "handoff", Direction::Push)[0].is_ok());
+    let zip_entries = entries(&root.join("out/handoff.zip")).join("\n");
+    assert_eq!(archive::listing(&zip_entries, Some("src")), "main.rs");
+    assert_eq!(
+        archive::listing(&zip_entries, Some("notes.txt")),
+        "notes.txt"
+    );
+    let listing = core::ls(
+        &linked.folders["handoff"],
+        &linked.ssh_servers,
+        &Some("src".to_string()),
+    )
+    .unwrap();
+    assert_eq!(listing.lines, ["main.rs"]);
+    let handoff = endpoint::get(&linked.folders["handoff"], &linked.ssh_servers).unwrap();
+    assert!(handoff.remove(&["src".to_string()]).is_err());
+
+    let missing_path = config(&root, "handoff.zip", r#""src", "docs""#);
+    assert_eq!(
+        run(&missing_path, "restore", Direction::Pull)[0]
+            .as_ref()
+            .unwrap_err(),
+        "Error: From Folder Does Not Exist"
+    );
+    assert_eq!(
+        run(&missing_path, "restore", Direction::Sync)[0]
+            .as_ref()
+            .unwrap_err(),
+        "Two way syncs are not supported for archive folders"
+    );
+}
@@ -0,0 +1,57 @@
+#![allow(clippy::needless_return, clippy::unused_unit, dead_code)]
+
+use directory_sync::service::config::parse_config;
+use directory_sync::service::logger;
+use directory_sync::{Config, Direction, SyncOptions, SyncReport, Syncer};
+use log::LevelFilter;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+
+static SETUP: Once = Once::new();
+
+// Shared by the tests of one file, under the temp folder
+pub fn scratch(suite: &str) -> PathBuf {
+    return env::temp_dir().join(format!("dsync-{}", suite));
+}
+
+// The scratch folder is emptied and made HOME once per file, before prepare adds what the file
+// needs. Each test then gets a fresh tree of dirs under <scratch>/<test>.
+pub fn setup(suite: &str, test: &str, dirs: &[&str], prepare: impl FnOnce(&Path)) -> PathBuf {
+    SETUP.call_once(|| {
+        let _ = fs::remove_dir_all(scratch(suite));
+        fs::create_dir_all(scratch(suite)).expect("Scratch folder");
+        env::set_var("HOME", scratch(suite));
+        logger::init(LevelFilter::Warn, &None).expect("Logger");
+        prepare(&scratch(suite));
+    });
+    let root = scratch(suite).join(test);
+    let _ = fs::remove_dir_all(&root);
+    for dir in dirs {
+        fs::create_dir_all(root.join(dir)).expect("Scratch folder");
+    }
+    return root;
+}
+
+pub fn config(toml: String) -> Config {
+    return parse_config(toml).expect("Parse config");
+}
+
+pub fn write(path: PathBuf, contents: &str) -> () {
+    fs::create_dir_all(path.parent().unwrap()).expect("Parent folder");
+    fs::write(path, contents).expect("Write file");
+}
+
+pub fn read(path: PathBuf) -> String {
+    return fs::read_to_string(path).expect("Read file");
+}
+
+pub fn run(config: &Config, link: &str, direction: Direction) -> Vec<Result<SyncReport, String>> {
+    return Syncer::new(config)
+        .options(SyncOptions {
+            force: true,
+            ..SyncOptions::default()
+        })
+        .run_link(link, direction, &None);
+}
@@ -0,0 +1,269 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+mod common;
+
+use chrono::{Duration, SecondsFormat, Utc};
+use common::{read, run, write};
+use directory_sync::model::lock::LockOwner;
+use directory_sync::service::config::parse_config;
+use directory_sync::service::core;
+use directory_sync::service::history;
+use directory_sync::service::lock;
+use directory_sync::service::tar;
+use directory_sync::{Config, Direction, SyncOptions, Syncer};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SUITE: &str = "loopback";
+
+// A scratch tree per test, with the "box" server living under <root>/box
+fn setup(test: &str) -> PathBuf {
+    let dirs = ["work", "project", "box/srv/work", "box/srv/project"];
+    return common::setup(SUITE, test, &dirs, |_| ());
+}
+
+fn config(root: &Path, transfer: &str) -> Config {
+    let root = root.display();
+    return common::config(format!(
+        r#"
+local_work_dir = "work"
+
+[folders.work]
+path = "{root}/work"
+target = "local"
+
+[folders.project_local]
+path = "{root}/project"
+target = "local"
+
+[folders.box_work]
+path = "/srv/work"
+target = "ssh"
+ssh_key = "box"
+
+[folders.project]
+path = "/srv/project"
+target = "ssh"
+ssh_key = "box"
+
+[links.project]
+local = "project_local"
+target = "project"
+paths = ["src"]
+partial_only = "false"
+exclude = ["*.log"]
+
+[ssh.box]
+host = "box.example.com"
+username = "me"
+work_dir = "box_work"
+loopback = "{root}/box"
+compression = "none"
+transfer = "{transfer}"
+"#
+    ));
+}
+
+fn sync(config: &Config, direction: Direction) -> () {
+    let reports = run(config, "project", direction);
+    assert_eq!(reports.len(), 1);
+    assert!(reports[0].is_ok(), "{:?}", reports[0]);
+}
+
+#[test]
+fn folders_map_under_the_root() {
+    let root = setup("folders_map_under_the_root");
+    let config = config(&root, "staged");
+    assert_eq!(
+        config.folders["project"].path,
+        format!("{}/box/srv/project", root.display())
+    );
+    assert_eq!(
+        config.ssh_servers["box"].work_folder.as_ref().unwrap().path,
+        format!("{}/box/srv/work", root.display())
+    );
+    assert_eq!(
+        config.folders["project_local"].path,
+        format!("{}/project", root.display())
+    );
+}
+
+#[test]
+fn staged_push_and_pull() {
+    let root = setup("staged_push_and_pull");
+    let config = config(&root, "staged");
+    write(root.join("project/src/main.rs"), "fn main() {}\n");
+    write(root.join("project/src/it's spaced/notes.txt"), "quoted\n");
+    write(root.join("project/src/debug.log"), "left out\n");
+    write(root.join("box/srv/project/src/stale.txt"), "replaced\n");
+
+    sync(&config, Direction::Push);
+    let pushed = root.join("box/srv/project/src");
+    assert_eq!(read(pushed.join("main.rs")), "fn main() {}\n");
+    assert_eq!(read(pushed.join("it's spaced/notes.txt")), "quoted\n");
+    assert!(!pushed.join("debug.log").exists());
+    assert!(!pushed.join("stale.txt").exists());
+    // Archives and locks are cleaned up on both sides
+    assert_eq!(fs::read_dir(root.join("work")).unwrap().count(), 0);
+    assert_eq!(fs::read_dir(root.join("box/srv/work")).unwrap().count(), 0);
+
+    write(pushed.join("main.rs"), "fn main() { edited() }\n");
+    sync(&config, Direction::Pull);
+    assert_eq!(
+        read(root.join("project/src/main.rs")),
+        "fn main() { edited() }\n"
+    );
+}
+
+#[test]
+fn streamed_push() {
+    let root = setup("streamed_push");
+    let config = config(&root, "stream");
+    write(root.join("project/src/lib.rs"), "pub fn lib() {}\n");
+
+    sync(&config, Direction::Push);
+    assert_eq!(
+        read(root.join("box/srv/project/src/lib.rs")),
+        "pub fn lib() {}\n"
+    );
+}
+
+#[test]
+fn resume_rebuilds_changed_source() {
+    let root = setup("resume_rebuilds_changed_source");
+    let config = config(&root, "staged");
+    write(root.join("project/src/main.rs"), "fn main() {}\n");
+    let session = tar::session_name(
+        &config.folders["project_local"],
+        &format!("{}/project", root.display()),
+        &config.folders["project"],
+        &format!("{}/box/srv/project", root.display()),
+        &["src".to_string()],
+    );
+    // A directory in the way of the copy interrupts the push once the archive is sealed
+    let copy_path = root.join(format!("box/srv/work/{}.tar", session));
+    fs::create_dir_all(copy_path.join("in_the_way")).unwrap();
+    assert!(run(&config, "project", Direction::Push)[0].is_err());
+    assert!(root.join(format!("work/{}.tar", session)).is_file());
+
+    fs::remove_dir_all(&copy_path).unwrap();
+    write(root.join("project/src/main.rs"), "fn main() { edited() }\n");
+    sync(&config, Direction::Push);
+    assert_eq!(
+        read(root.join("box/srv/project/src/main.rs")),
+        "fn main() { edited() }\n"
+    );
+    assert_eq!(fs::read_dir(root.join("work")).unwrap().count(), 0);
+}
+
+#[test]
+fn ls_and_missing_paths() {
+    let root = setup("ls_and_missing_paths");
+    let config = config(&root, "staged");
+    write(root.join("box/srv/project/src/main.rs"), "fn main() {}\n");
+
+    let ls = core::ls(
+        &config.folders["project"],
+        &config.ssh_servers,
+        &Some("src".to_string()),
+    );
+    assert!(ls.unwrap().lines.last().unwrap().ends_with(" main.rs"));
+    let ls = core::ls(
+        &config.folders["project"],
+        &config.ssh_servers,
+        &Some("missing".to_string()),
+    );
+    assert!(ls.is_err());
+
+    let reports = Syncer::new(&config)
+        .options(SyncOptions {
+            force: true,
+            ..SyncOptions::default()
+        })
+        .run_folders(
+            &config.folders["project"],
+            &config.folders["project_local"],
+            &Some("missing".to_string()),
+        );
+    assert_eq!(
+        reports[0].as_ref().unwrap_err(),
+        "Error: From Folder Does Not Exist"
+    );
+}
+
+#[test]
+fn stale_locks_are_taken_over_live_ones_kept() {
+    let root = setup("stale_locks_are_taken_over_live_ones_kept");
+    let config = config(&root, "staged");
+    let folder = &config.folders["project"];
+    let lock_path = lock::lock_path(folder, &folder.path, &None, &config.ssh_servers).unwrap();
+    let since = (Utc::now() - Duration::hours(48)).to_rfc3339_opts(SecondsFormat::Secs, true);
+    let owner = |pid: u32| LockOwner {
+        user: history::user(),
+        host: history::host(),
+        pid,
+        since: since.clone(),
+    };
+
+    // Still running on this host, however old
+    write(
+        PathBuf::from(&lock_path),
+        &serde_json::to_string(&owner(std::process::id())).unwrap(),
+    );
+    let locked = lock::acquire(folder, &lock_path, &config.ssh_servers, false);
+    assert!(locked.unwrap_err().starts_with("Destination is locked by"));
+
+    // Gone from this host
+    write(
+        PathBuf::from(&lock_path),
+        &serde_json::to_string(&owner(u32::MAX)).unwrap(),
+    );
+    lock::acquire(folder, &lock_path, &config.ssh_servers, false).unwrap();
+    let holder: LockOwner = serde_json::from_str(read(PathBuf::from(&lock_path)).trim()).unwrap();
+    assert_eq!(holder.pid, std::process::id());
+    lock::release(folder, &lock_path, &config.ssh_servers);
+    assert_eq!(fs::read_dir(root.join("box/srv/work")).unwrap().count(), 0);
+}
+
+#[test]
+fn invalid_config_is_an_error() {
+    let missing_work_folder = parse_config(
+        r#"
+local_work_dir = "work"
+folders = {}
+links = {}
+ssh = {}
+"#
+        .to_string(),
+    );
+    assert_eq!(
+        missing_work_folder.unwrap_err(),
+        "Local work folder not found: work"
+    );
+    assert!(parse_config("folders = [".to_string())
+        .unwrap_err()
+        .starts_with("Error parsing config"));
+}
+
+#[test]
+fn two_way_sync_batches_copies_and_prunes_emptied_folders() {
+    let root = setup("two_way_sync_batches_copies_and_prunes_emptied_folders");
+    let config = config(&root, "staged");
+    // More path bytes than one command takes
+    for index in 0..1500 {
+        write(
+            root.join(format!("project/src/many/{:0>60}.txt", index)),
+            "x\n",
+        );
+    }
+    write(root.join("project/src/gone/deep/only.txt"), "removed\n");
+    sync(&config, Direction::Sync);
+    let remote = root.join("box/srv/project/src");
+    assert_eq!(fs::read_dir(remote.join("many")).unwrap().count(), 1500);
+    assert_eq!(read(remote.join("gone/deep/only.txt")), "removed\n");
+
+    fs::remove_dir_all(root.join("project/src/gone")).unwrap();
+    sync(&config, Direction::Sync);
+    assert!(!remote.join("gone").exists());
+    assert!(remote.join("many").is_dir());
+}
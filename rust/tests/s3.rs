@@ -0,0 +1,236 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+mod common;
+
+use common::{run, scratch, write};
+use directory_sync::service::core;
+use directory_sync::service::endpoint;
+use directory_sync::{Config, Direction};
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+// Stands in for the aws cli, keeping buckets as directories under $DSYNC_TEST_S3, their
+// metadata under $DSYNC_TEST_S3.meta and a log of every call next to them. ETags look like
+// those of multipart uploads, which dsync cannot compute.
+const FAKE_AWS: &str = r#"#!/bin/sh
+echo "$*" >> "$DSYNC_TEST_S3.log"
+while [ "${1#--}" != "$1" ]; do shift 2; done
+command="$1 $2"
+shift 2
+case "$command" in
+"s3 ls")
+    path=${2#s3://}
+    bucket=${path%%/*}
+    cd "$DSYNC_TEST_S3/$bucket" || exit 1
+    find . -type f -path "./${path#*/}*" | sed 's|^\./||' | while read -r key; do
+        echo "2026-01-01 00:00:00 $(wc -c < "$key") $key"
+    done
+    ;;
+"s3api head-object")
+    while [ $# -gt 0 ]; do
+        case "$1" in
+        --bucket) bucket=$2 ;;
+        --key) key=$2 ;;
+        --query) query=$2 ;;
+        esac
+        shift 2
+    done
+    object="$DSYNC_TEST_S3/$bucket/$key"
+    [ -f "$object" ] || { echo "Not Found" >&2; exit 254; }
+    etag=$(md5sum < "$object" | cut -c1-32)-1
+    case "$query" in
+    Metadata.*)
+        metadata=$(cat "$DSYNC_TEST_S3.meta/$bucket/$key" 2>/dev/null)
+        echo "${metadata#*=}" ;;
+    *) printf '"%s"\t%s\n' "$etag" "$(wc -c < "$object")" ;;
+    esac
+    ;;
+"s3 cp")
+    metadata=
+    while [ "${1#--}" != "$1" ]; do
+        case "$1" in
+        --metadata) metadata=$2 && shift ;;
+        esac
+        shift
+    done
+    case "$1" in
+    s3://*) cp "$DSYNC_TEST_S3/${1#s3://}" "$2" ;;
+    *)
+        mkdir -p "$(dirname "$DSYNC_TEST_S3/${2#s3://}")" "$(dirname "$DSYNC_TEST_S3.meta/${2#s3://}")"
+        cp "$1" "$DSYNC_TEST_S3/${2#s3://}"
+        echo "$metadata" > "$DSYNC_TEST_S3.meta/${2#s3://}"
+        ;;
+    esac
+    ;;
+*)
+    echo "Unsupported: $command" >&2
+    exit 2
+    ;;
+esac
+"#;
+
+const SUITE: &str = "s3";
+
+// A scratch tree per test, its objects are kept under <prefix>/ in the "lab" bucket.
+// Archives are left uncompressed so zstd is not needed.
+fn setup(test: &str) -> PathBuf {
+    return common::setup(SUITE, test, &["work", "project", "restore"], |scratch| {
+        let bin = scratch.join("bin");
+        fs::create_dir_all(&bin).expect("Scratch bin");
+        fs::write(bin.join("aws"), FAKE_AWS).expect("Fake aws");
+        fs::set_permissions(bin.join("aws"), fs::Permissions::from_mode(0o755))
+            .expect("Executable aws");
+        fs::create_dir_all(scratch.join("buckets/lab")).expect("Scratch bucket");
+        env::set_var("DSYNC_TEST_S3", scratch.join("buckets"));
+        env::set_var(
+            "PATH",
+            format!("{}:{}", bin.display(), env::var("PATH").unwrap_or_default()),
+        );
+    });
+}
+
+fn config(root: &Path, prefix: &str) -> Config {
+    let root = root.display();
+    return common::config(format!(
+        r#"
+local_work_dir = "work"
+
+[folders.work]
+path = "{root}/work"
+target = "local"
+
+[folders.project]
+path = "{root}/project"
+target = "local"
+
+[folders.restore]
+path = "{root}/restore"
+target = "local"
+
+[folders.archive]
+path = "{prefix}"
+target = "s3"
+bucket = "lab"
+endpoint = "http://localhost:9000"
+
+[links.archive]
+local = "project"
+target = "archive"
+paths = ["src"]
+partial_only = "false"
+compression = "none"
+exclude = ["*.log"]
+
+[links.restore]
+local = "restore"
+target = "archive"
+paths = ["src"]
+partial_only = "false"
+compression = "none"
+
+[ssh]
+"#
+    ));
+}
+
+// Calls the fake aws got that mention the key
+fn aws_calls(key: &str) -> Vec<String> {
+    let log = fs::read_to_string(scratch(SUITE).join("buckets.log")).unwrap_or_default();
+    return log
+        .lines()
+        .filter(|line| line.contains(key))
+        .map(|line| line.to_string())
+        .collect();
+}
+
+#[test]
+fn push_uploads_only_changes() {
+    let root = setup("push_uploads_only_changes");
+    let config = config(&root, "push");
+    write(root.join("project/src/data.csv"), "a,b\n1,2\n");
+    write(root.join("project/src/debug.log"), "left out\n");
+
+    assert!(run(&config, "archive", Direction::Push)[0].is_ok());
+    let object = scratch(SUITE).join("buckets/lab/push/src.tar");
+    assert!(object.exists());
+    assert_eq!(fs::read_dir(root.join("work")).unwrap().count(), 0);
+    let uploads = |calls: Vec<String>| calls.iter().filter(|call| call.contains(" s3 cp ")).count();
+    assert_eq!(uploads(aws_calls("push/src.tar")), 1);
+
+    // The same archive has the same hash, so it is not uploaded again
+    let reports = run(&config, "archive", Direction::Push);
+    let report = reports[0].as_ref().unwrap();
+    assert_eq!((report.files, report.bytes), (0, 0));
+    assert_eq!(uploads(aws_calls("push/src.tar")), 1);
+
+    write(root.join("project/src/data.csv"), "a,b\n3,4\n");
+    assert!(run(&config, "archive", Direction::Push)[0].is_ok());
+    assert_eq!(uploads(aws_calls("push/src.tar")), 2);
+}
+
+#[test]
+fn pull_extracts_and_skips_unchanged() {
+    let root = setup("pull_extracts_and_skips_unchanged");
+    let config = config(&root, "pull");
+    write(root.join("project/src/data.csv"), "a,b\n1,2\n");
+    write(root.join("restore/src/stale.csv"), "replaced\n");
+    assert!(run(&config, "archive", Direction::Push)[0].is_ok());
+
+    let reports = run(&config, "restore", Direction::Pull);
+    assert!(reports[0].as_ref().unwrap().bytes > 0);
+    assert_eq!(
+        fs::read_to_string(root.join("restore/src/data.csv")).unwrap(),
+        "a,b\n1,2\n"
+    );
+    assert!(!root.join("restore/src/stale.csv").exists());
+    let downloads = |calls: Vec<String>| {
+        calls
+            .iter()
+            .filter(|call| call.contains(" s3 cp --no-progress s3://"))
+            .count()
+    };
+    assert_eq!(downloads(aws_calls("pull/src.tar")), 1);
+
+    // Unchanged since the last pull, nothing is downloaded
+    let reports = run(&config, "restore", Direction::Pull);
+    assert_eq!(reports[0].as_ref().unwrap().bytes, 0);
+    assert_eq!(downloads(aws_calls("pull/src.tar")), 1);
+
+    write(root.join("project/src/data.csv"), "a,b\n3,4\n");
+    assert!(run(&config, "archive", Direction::Push)[0].is_ok());
+    assert!(run(&config, "restore", Direction::Pull)[0].is_ok());
+    assert_eq!(downloads(aws_calls("pull/src.tar")), 2);
+    assert_eq!(
+        fs::read_to_string(root.join("restore/src/data.csv")).unwrap(),
+        "a,b\n3,4\n"
+    );
+}
+
+#[test]
+fn missing_objects_and_unsupported_syncs() {
+    let root = setup("missing_objects_and_unsupported_syncs");
+    let config = config(&root, "missing");
+
+    assert_eq!(
+        run(&config, "restore", Direction::Pull)[0]
+            .as_ref()
+            .unwrap_err(),
+        "Error: From Folder Does Not Exist"
+    );
+    assert!(core::ls(
+        &config.folders["archive"],
+        &config.ssh_servers,
+        &Some("src".to_string())
+    )
+    .is_ok());
+    let bucket = endpoint::get(&config.folders["archive"], &config.ssh_servers).unwrap();
+    assert!(bucket.remove(&["src".to_string()]).is_err());
+    assert_eq!(
+        run(&config, "restore", Direction::Sync)[0]
+            .as_ref()
+            .unwrap_err(),
+        "Two way syncs are not supported for s3 folders"
+    );
+}
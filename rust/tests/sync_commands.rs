@@ -0,0 +1,661 @@
+#![allow(clippy::needless_return)]
+
+use directory_sync::model::bisync::{ConflictResolution, SyncState};
+use directory_sync::model::checksum::ChecksumAlgorithm;
+use directory_sync::model::compression::{Compression, CompressionKind};
+use directory_sync::model::folder::FolderType;
+use directory_sync::model::hook::Hooks;
+use directory_sync::model::manifest::ManifestEntry;
+use directory_sync::model::transfer::TransferMode;
+use directory_sync::model::transport::Transport;
+use directory_sync::service::bisync;
+use directory_sync::service::config::parse_config;
+use directory_sync::service::history;
+use directory_sync::service::link;
+use directory_sync::service::logger;
+use directory_sync::service::prompt::{self, Prompt};
+use directory_sync::service::runner::{self, CommandRunner, RecordingRunner};
+use directory_sync::{Config, Folder, Link, SshServer, SyncOptions, SyncReport, Syncer};
+use log::LevelFilter;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::io;
+use std::process::{ExitStatus, Output};
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex, MutexGuard, Once};
+
+// The runner is process wide, so tests take turns
+static RUNNER: Mutex<()> = Mutex::new(());
+static SETUP: Once = Once::new();
+
+fn setup() -> MutexGuard<'static, ()> {
+    let guard = RUNNER
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    SETUP.call_once(|| {
+        // History lands in a scratch home, and no progress bars probe the archive
+        env::set_var("HOME", env::temp_dir().join("dsync-sync-commands"));
+        std::fs::create_dir_all(env::temp_dir().join("dsync-sync-commands")).expect("Scratch home");
+        logger::init(LevelFilter::Warn, &None).expect("Logger");
+    });
+    return guard;
+}
+
+fn folder(name: &str, path: &str, ssh_key: Option<&str>) -> Folder {
+    let target = match ssh_key {
+        Some(_) => FolderType::Ssh,
+        None => FolderType::Local,
+    };
+    return Folder::new(
+        name.to_string(),
+        path.to_string(),
+        target,
+        ssh_key.map(|ssh_key| ssh_key.to_string()),
+    );
+}
+
+fn ssh_server(key: &str, host: &str, work_path: &str, transport: Option<Transport>) -> SshServer {
+    return SshServer {
+        key: key.to_string(),
+        host: host.to_string(),
+        username: "me".to_string(),
+        port: 2222,
+        work_folder: Some(folder(&format!("{}_work", key), work_path, Some(key))),
+        compression: None,
+        link_speed: None,
+        transfer: None,
+        checksum: None,
+        bandwidth_limit: None,
+        low_priority: None,
+        loopback: None,
+        transport,
+    };
+}
+
+fn template(args: &[&str]) -> Vec<String> {
+    return args.iter().map(|arg| arg.to_string()).collect();
+}
+
+fn config() -> Config {
+    let mut box_server = ssh_server("box", "box.example.com", "/srv/work", None);
+    box_server.compression = Some(Compression {
+        kind: CompressionKind::Zstd,
+        level: Some(3),
+    });
+    box_server.checksum = Some(ChecksumAlgorithm::Sha256);
+    let ssh_servers = [
+        box_server,
+        // Reached through teleport, which has its own scp
+        ssh_server(
+            "tele",
+            "box.example.com",
+            "/srv/work",
+            Some(Transport {
+                exec: template(&["tsh", "ssh", "{user}@{host}"]),
+                copy: Some(template(&["tsh", "scp", "-r", "{from}", "{to}"])),
+                remote_path: "{user}@{host}:{path}".to_string(),
+            }),
+        ),
+        // A pod only reached with kubectl exec, so archives are streamed through it
+        ssh_server(
+            "pod",
+            "web-0",
+            "/tmp/work",
+            Some(Transport {
+                exec: template(&[
+                    "kubectl",
+                    "exec",
+                    "-i",
+                    "{host}",
+                    "--",
+                    "sh",
+                    "-c",
+                    "{command}",
+                ]),
+                copy: None,
+                remote_path: "{user}@{host}:{path}".to_string(),
+            }),
+        ),
+    ];
+    let mut folders = HashMap::new();
+    for folder in [
+        folder("work", "/home/me/work", None),
+        folder("project_local", "/home/me/project", None),
+        folder("project_copy", "/home/me/copy", None),
+        folder("project", "/srv/project", Some("box")),
+        folder("project_tele", "/srv/project", Some("tele")),
+        folder("project_pod", "/app", Some("pod")),
+    ] {
+        folders.insert(folder.name.clone(), folder);
+    }
+    return Config {
+        ssh_servers: ssh_servers
+            .into_iter()
+            .map(|ssh_server| (ssh_server.key.clone(), ssh_server))
+            .collect(),
+        work_folder: folders.get("work").cloned(),
+        folders,
+        links: HashMap::new(),
+    };
+}
+
+// Each command joined into one line, with the lock owner left out as it holds a pid and time
+// and the source fingerprint script as it is long
+fn run(
+    from: &str,
+    to: &str,
+    runner: RecordingRunner,
+) -> (Vec<Result<SyncReport, String>>, Vec<String>) {
+    return run_config(config(), from, to, runner);
+}
+
+fn run_config(
+    config: Config,
+    from: &str,
+    to: &str,
+    runner: RecordingRunner,
+) -> (Vec<Result<SyncReport, String>>, Vec<String>) {
+    let runner = Arc::new(runner);
+    runner::set(Some(runner.clone()));
+    let results = Syncer::new(&config)
+        .options(SyncOptions {
+            force: true,
+            ..SyncOptions::default()
+        })
+        .run_folders(
+            &config.folders[from],
+            &config.folders[to],
+            &Some("src".to_string()),
+        );
+    runner::set(None);
+    let commands = runner
+        .commands()
+        .iter()
+        .map(|cmd_args| {
+            cmd_args
+                .iter()
+                .map(|arg| match arg {
+                    _ if arg.contains("\"pid\":") => "<owner>",
+                    _ if arg.contains("| cksum") => "<fingerprint>",
+                    _ => arg.as_str(),
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect();
+    return (results, commands);
+}
+
+#[test]
+fn local_sync() {
+    let _guard = setup();
+    let (results, commands) = run("project_local", "project_copy", RecordingRunner::new());
+    assert!(results[0].is_ok());
+    assert_eq!(
+        commands,
+        [
+            "stat -c %s %a %Y %n /home/me/project/src",
+            "find /home/me/project/src -type f",
+            "du -sck /home/me/project/src",
+            "wc -c /home/me/work/dsync-b0423c86aaa46b8c.tar",
+            "sh -c set -C; printf '%s\\n' \"$1\" > \"$2\" sh <owner> /home/me/work/dsync-398ccbd27b90ac1d.lock",
+            "sh -c <fingerprint> sh /home/me/work/dsync-b0423c86aaa46b8c.tar.source /home/me/project/src",
+            "tar -cf /home/me/work/dsync-b0423c86aaa46b8c.tar.part -C /home/me/project src",
+            "mv /home/me/work/dsync-b0423c86aaa46b8c.tar.part /home/me/work/dsync-b0423c86aaa46b8c.tar",
+            "mkdir -p /home/me/copy/src",
+            "ls /home/me/work/dsync-b0423c86aaa46b8c.tar",
+            "rm -rf /home/me/copy/src",
+            "tar -xf /home/me/work/dsync-b0423c86aaa46b8c.tar -C /home/me/copy",
+            "rm /home/me/work/dsync-b0423c86aaa46b8c.tar /home/me/work/dsync-b0423c86aaa46b8c.tar.source",
+            "rm -f /home/me/work/dsync-398ccbd27b90ac1d.lock",
+        ]
+    );
+}
+
+#[test]
+fn push_to_ssh() {
+    let _guard = setup();
+    let (results, commands) = run("project_local", "project", RecordingRunner::new());
+    assert!(results[0].is_ok());
+    assert_eq!(
+        commands,
+        [
+            "stat -c %s %a %Y %n /home/me/project/src",
+            "find /home/me/project/src -type f",
+            "du -sck /home/me/project/src",
+            "wc -c /home/me/work/dsync-c3feb5f7ff9c8f54.tar.zst",
+            "ssh -p 2222 me@box.example.com sh -c 'set -C; printf '\\''%s\\n'\\'' \"$1\" > \"$2\"' sh <owner> /srv/work/dsync-65927469d9b6cded.lock",
+            "sh -c <fingerprint> sh /home/me/work/dsync-c3feb5f7ff9c8f54.tar.zst.source /home/me/project/src",
+            "tar --use-compress-program=zstd -3 -cf /home/me/work/dsync-c3feb5f7ff9c8f54.tar.zst.part -C /home/me/project src",
+            "mv /home/me/work/dsync-c3feb5f7ff9c8f54.tar.zst.part /home/me/work/dsync-c3feb5f7ff9c8f54.tar.zst",
+            "scp -P 2222 -r /home/me/work/dsync-c3feb5f7ff9c8f54.tar.zst me@box.example.com:/srv/work/dsync-c3feb5f7ff9c8f54.tar.zst",
+            "ssh -p 2222 me@box.example.com mkdir -p /srv/project/src",
+            "sha256sum /home/me/work/dsync-c3feb5f7ff9c8f54.tar.zst",
+            "ssh -p 2222 me@box.example.com sha256sum /srv/work/dsync-c3feb5f7ff9c8f54.tar.zst",
+            "ssh -p 2222 me@box.example.com rm -rf /srv/project/src",
+            "ssh -p 2222 me@box.example.com tar --use-compress-program=zstd -xf /srv/work/dsync-c3feb5f7ff9c8f54.tar.zst -C /srv/project",
+            "ssh -p 2222 me@box.example.com rm /srv/work/dsync-c3feb5f7ff9c8f54.tar.zst",
+            "rm /home/me/work/dsync-c3feb5f7ff9c8f54.tar.zst /home/me/work/dsync-c3feb5f7ff9c8f54.tar.zst.source",
+            "ssh -p 2222 me@box.example.com rm -f /srv/work/dsync-65927469d9b6cded.lock",
+        ]
+    );
+}
+
+#[test]
+fn pull_from_ssh() {
+    let _guard = setup();
+    let (results, commands) = run("project", "project_local", RecordingRunner::new());
+    assert!(results[0].is_ok());
+    assert_eq!(
+        commands,
+        [
+            "ssh -p 2222 me@box.example.com stat -c '%s %a %Y %n' /srv/project/src",
+            "ssh -p 2222 me@box.example.com find /srv/project/src -type f",
+            "ssh -p 2222 me@box.example.com du -sck /srv/project/src",
+            "ssh -p 2222 me@box.example.com wc -c /srv/work/dsync-e128f7b71838f472.tar.zst",
+            "sh -c set -C; printf '%s\\n' \"$1\" > \"$2\" sh <owner> /home/me/work/dsync-aa2c9e4fcfd08caf.lock",
+            "ssh -p 2222 me@box.example.com sh -c <fingerprint> sh /srv/work/dsync-e128f7b71838f472.tar.zst.source /srv/project/src",
+            "ssh -p 2222 me@box.example.com tar '--use-compress-program=zstd -3' -cf /srv/work/dsync-e128f7b71838f472.tar.zst.part -C /srv/project src",
+            "ssh -p 2222 me@box.example.com mv /srv/work/dsync-e128f7b71838f472.tar.zst.part /srv/work/dsync-e128f7b71838f472.tar.zst",
+            "scp -P 2222 -r me@box.example.com:/srv/work/dsync-e128f7b71838f472.tar.zst /home/me/work/dsync-e128f7b71838f472.tar.zst",
+            "mkdir -p /home/me/project/src",
+            "ssh -p 2222 me@box.example.com sha256sum /srv/work/dsync-e128f7b71838f472.tar.zst",
+            "sha256sum /home/me/work/dsync-e128f7b71838f472.tar.zst",
+            "rm -rf /home/me/project/src",
+            "tar --use-compress-program=zstd -xf /home/me/work/dsync-e128f7b71838f472.tar.zst -C /home/me/project",
+            "rm /home/me/work/dsync-e128f7b71838f472.tar.zst",
+            "ssh -p 2222 me@box.example.com rm /srv/work/dsync-e128f7b71838f472.tar.zst /srv/work/dsync-e128f7b71838f472.tar.zst.source",
+            "rm -f /home/me/work/dsync-aa2c9e4fcfd08caf.lock",
+        ]
+    );
+}
+
+#[test]
+fn missing_source_runs_nothing() {
+    let _guard = setup();
+    let runner = RecordingRunner::new().fail("/home/me/project/src");
+    let (results, commands) = run("project_local", "project", runner);
+    assert_eq!(
+        results[0].as_ref().unwrap_err(),
+        "Error: From Folder Does Not Exist"
+    );
+    assert_eq!(commands, ["stat -c %s %a %Y %n /home/me/project/src"]);
+}
+
+#[test]
+fn unknown_ssh_server_runs_nothing() {
+    let _guard = setup();
+    let mut config = config();
+    let typo = folder("typo", "/srv/project", Some("nosuch"));
+    config.folders.insert(typo.name.clone(), typo);
+    let (results, commands) = run_config(config, "project_local", "typo", RecordingRunner::new());
+    assert_eq!(
+        results[0].as_ref().unwrap_err(),
+        "No SSH Server Found for ssh_key nosuch"
+    );
+    assert!(commands.is_empty());
+
+    let parsed = parse_config(
+        r#"
+[folders.typo]
+path = "/srv/project"
+target = "ssh"
+ssh_key = "nosuch"
+
+[links]
+
+[ssh]
+"#
+        .to_string(),
+    );
+    assert_eq!(
+        parsed.err().unwrap(),
+        "Ssh server not found for folder typo: nosuch"
+    );
+}
+
+// Stands in for a machine without ssh or aws installed
+struct MissingPrograms;
+
+impl CommandRunner for MissingPrograms {
+    fn output(&self, _: &[String]) -> io::Result<Output> {
+        return Err(io::Error::from(io::ErrorKind::NotFound));
+    }
+
+    fn run(&self, _: &[String]) -> io::Result<ExitStatus> {
+        return Err(io::Error::from(io::ErrorKind::NotFound));
+    }
+
+    fn pipe(
+        &self,
+        _: &[String],
+        _: &[String],
+        _: &AtomicU64,
+        _: Option<u32>,
+    ) -> io::Result<ExitStatus> {
+        return Err(io::Error::from(io::ErrorKind::NotFound));
+    }
+}
+
+#[test]
+fn missing_program_fails_the_sync() {
+    let _guard = setup();
+    let config = config();
+    runner::set(Some(Arc::new(MissingPrograms)));
+    let results = Syncer::new(&config)
+        .options(SyncOptions {
+            force: true,
+            ..SyncOptions::default()
+        })
+        .run_folders(
+            &config.folders["project"],
+            &config.folders["project_local"],
+            &Some("src".to_string()),
+        );
+    runner::set(None);
+    assert!(results[0]
+        .as_ref()
+        .unwrap_err()
+        .starts_with("Failed to Check Folder: "));
+}
+
+#[test]
+fn failed_step_releases_lock() {
+    let _guard = setup();
+    let runner = RecordingRunner::new().fail("/home/me/work/dsync-c3feb5f7ff9c8f54.tar.zst.part");
+    let (results, commands) = run("project_local", "project", runner);
+    assert_eq!(results[0].as_ref().unwrap_err(), "Failed to Create Tar");
+    assert_eq!(
+        commands[4..],
+        [
+            "ssh -p 2222 me@box.example.com sh -c 'set -C; printf '\\''%s\\n'\\'' \"$1\" > \"$2\"' sh <owner> /srv/work/dsync-65927469d9b6cded.lock",
+            "sh -c <fingerprint> sh /home/me/work/dsync-c3feb5f7ff9c8f54.tar.zst.source /home/me/project/src",
+            "tar --use-compress-program=zstd -3 -cf /home/me/work/dsync-c3feb5f7ff9c8f54.tar.zst.part -C /home/me/project src",
+            "ssh -p 2222 me@box.example.com rm -f /srv/work/dsync-65927469d9b6cded.lock",
+        ]
+    );
+}
+
+#[test]
+fn pre_sync_runs_before_planning() {
+    let _guard = setup();
+    let mut config = config();
+    config.folders.get_mut("project_local").unwrap().hooks =
+        Hooks::new(Some("make".to_string()), None, None);
+    let (results, commands) =
+        run_config(config, "project_local", "project", RecordingRunner::new());
+    assert!(results[0].is_ok());
+    assert!(commands[0].starts_with("env DSYNC_HOOK=pre_sync"));
+    assert!(commands[0].ends_with("sh -c cd \"$DSYNC_FOLDER\" && make"));
+    assert_eq!(commands[1], "stat -c %s %a %Y %n /home/me/project/src");
+}
+
+#[test]
+fn aborting_pre_sync_plans_nothing() {
+    let _guard = setup();
+    let mut config = config();
+    config.folders.get_mut("project_local").unwrap().hooks =
+        Hooks::new(Some("make".to_string()), None, None);
+    let runner = RecordingRunner::new().fail("cd \"$DSYNC_FOLDER\" && make");
+    let (results, commands) = run_config(config, "project_local", "project", runner);
+    assert!(results[0].as_ref().unwrap_err().ends_with("sync aborted"));
+    assert_eq!(commands.len(), 1);
+}
+
+#[test]
+fn push_through_transport() {
+    let _guard = setup();
+    let (results, commands) = run("project_local", "project_tele", RecordingRunner::new());
+    assert!(results[0].is_ok());
+    assert_eq!(
+        commands,
+        [
+            "stat -c %s %a %Y %n /home/me/project/src",
+            "find /home/me/project/src -type f",
+            "du -sck /home/me/project/src",
+            "wc -c /home/me/work/dsync-aa5baf94a42a8897.tar",
+            "tsh ssh me@box.example.com <owner>",
+            "sh -c <fingerprint> sh /home/me/work/dsync-aa5baf94a42a8897.tar.source /home/me/project/src",
+            "tar -cf /home/me/work/dsync-aa5baf94a42a8897.tar.part -C /home/me/project src",
+            "mv /home/me/work/dsync-aa5baf94a42a8897.tar.part /home/me/work/dsync-aa5baf94a42a8897.tar",
+            "tsh scp -r /home/me/work/dsync-aa5baf94a42a8897.tar me@box.example.com:/srv/work/dsync-aa5baf94a42a8897.tar",
+            "tsh ssh me@box.example.com mkdir -p /srv/project/src",
+            "tsh ssh me@box.example.com ls /srv/work/dsync-aa5baf94a42a8897.tar",
+            "tsh ssh me@box.example.com rm -rf /srv/project/src",
+            "tsh ssh me@box.example.com tar -xf /srv/work/dsync-aa5baf94a42a8897.tar -C /srv/project",
+            "tsh ssh me@box.example.com rm /srv/work/dsync-aa5baf94a42a8897.tar",
+            "rm /home/me/work/dsync-aa5baf94a42a8897.tar /home/me/work/dsync-aa5baf94a42a8897.tar.source",
+            "tsh ssh me@box.example.com rm -f /srv/work/dsync-e38c0068f6076c28.lock",
+        ]
+    );
+}
+
+#[test]
+fn pull_streamed_through_transport() {
+    let _guard = setup();
+    let (results, commands) = run("project_pod", "project_local", RecordingRunner::new());
+    assert!(results[0].is_ok());
+    assert_eq!(
+        commands,
+        [
+            "kubectl exec -i web-0 -- sh -c stat -c '%s %a %Y %n' /app/src",
+            "kubectl exec -i web-0 -- sh -c find /app/src -type f",
+            "kubectl exec -i web-0 -- sh -c du -sck /app/src",
+            "kubectl exec -i web-0 -- sh -c wc -c /tmp/work/dsync-e09b526e4a7cf850.tar",
+            "sh -c set -C; printf '%s\\n' \"$1\" > \"$2\" sh <owner> /home/me/work/dsync-aa2c9e4fcfd08caf.lock",
+            "kubectl exec -i web-0 -- sh -c <fingerprint>",
+            "kubectl exec -i web-0 -- sh -c tar -cf /tmp/work/dsync-e09b526e4a7cf850.tar.part -C /app src",
+            "kubectl exec -i web-0 -- sh -c mv /tmp/work/dsync-e09b526e4a7cf850.tar.part /tmp/work/dsync-e09b526e4a7cf850.tar",
+            "kubectl exec -i web-0 -- sh -c cat /tmp/work/dsync-e09b526e4a7cf850.tar",
+            "dd of=/home/me/work/dsync-e09b526e4a7cf850.tar status=none",
+            "mkdir -p /home/me/project/src",
+            "ls /home/me/work/dsync-e09b526e4a7cf850.tar",
+            "rm -rf /home/me/project/src",
+            "tar -xf /home/me/work/dsync-e09b526e4a7cf850.tar -C /home/me/project",
+            "rm /home/me/work/dsync-e09b526e4a7cf850.tar",
+            "kubectl exec -i web-0 -- sh -c rm /tmp/work/dsync-e09b526e4a7cf850.tar /tmp/work/dsync-e09b526e4a7cf850.tar.source",
+            "rm -f /home/me/work/dsync-aa2c9e4fcfd08caf.lock",
+        ]
+    );
+}
+
+#[test]
+fn transport_copy_keeps_the_bandwidth_limit() {
+    let _guard = setup();
+    let tele = |bandwidth_limit: Option<u32>, copy: &[&str]| {
+        let mut config = config();
+        let tele = config.ssh_servers.get_mut("tele").unwrap();
+        tele.bandwidth_limit = bandwidth_limit;
+        tele.transport.as_mut().unwrap().copy = Some(template(copy));
+        return run_config(
+            config,
+            "project_local",
+            "project_tele",
+            RecordingRunner::new(),
+        );
+    };
+
+    // The template cannot be limited, so the archive is paced through exec
+    let (results, commands) = tele(Some(800), &["tsh", "scp", "-r", "{from}", "{to}"]);
+    assert!(results[0].is_ok());
+    assert!(!commands
+        .iter()
+        .any(|command| command.starts_with("tsh scp")));
+    assert!(commands
+        .iter()
+        .any(|command| command.starts_with("tsh ssh me@box.example.com dd of=")));
+
+    let limited = ["tsh", "scp", "-l{bwlimit}", "-r", "{from}", "{to}"];
+    let (results, commands) = tele(Some(800), &limited);
+    assert!(results[0].is_ok());
+    assert!(commands
+        .iter()
+        .any(|command| command.starts_with("tsh scp -l800 -r /home/me/work/")));
+
+    let (results, commands) = tele(None, &limited);
+    assert!(results[0].is_ok());
+    assert!(commands
+        .iter()
+        .any(|command| command.starts_with("tsh scp -r /home/me/work/")));
+}
+
+#[test]
+fn unreachable_side_fails_two_way_sync() {
+    let _guard = setup();
+    let config = config();
+    let link = Link {
+        name: "unreachable".to_string(),
+        local: config.folders["project_local"].clone(),
+        target: config.folders["project"].clone(),
+        paths: Vec::new(),
+        partial_only: false,
+        compression: None,
+        transfer: None,
+        checksum: None,
+        verify: false,
+        low_priority: None,
+        jobs: None,
+        bundle: false,
+        exclude: Vec::new(),
+        schedule: None,
+        hooks: Hooks::default(),
+    };
+    let entry = ManifestEntry {
+        size: 3,
+        mode: "644".to_string(),
+        mtime: 1,
+        hash: Some("abc".to_string()),
+    };
+    let state = SyncState {
+        roots: BTreeMap::from([(
+            "src".to_string(),
+            BTreeMap::from([("a.txt".to_string(), entry)]),
+        )]),
+    };
+    bisync::save_state(&link.name, &state).expect("Saved state");
+
+    // The local file is unchanged since the last sync, while ssh cannot reach the remote
+    let runner = Arc::new(
+        RecordingRunner::new()
+            .respond("%s %a %Y %n", "3 644 1 /home/me/project/src/a.txt\n")
+            .respond("sha256sum", "abc  /home/me/project/src/a.txt\n")
+            .fail_with("/srv/project/src", 255),
+    );
+    runner::set(Some(runner.clone()));
+    let results = bisync::sync(
+        &link,
+        &config.work_folder,
+        &config.ssh_servers,
+        &Some("src".to_string()),
+        &SyncOptions {
+            force: true,
+            ..SyncOptions::default()
+        },
+    );
+    runner::set(None);
+    assert!(results[0]
+        .as_ref()
+        .unwrap_err()
+        .starts_with("Failed to Check Folder: /srv/project/src"));
+    // Only the locks are removed, a.txt is not deleted on either side
+    assert!(!runner
+        .commands()
+        .iter()
+        .flatten()
+        .any(|arg| arg.ends_with("a.txt")));
+    assert_eq!(bisync::load_state(&link.name), state);
+}
+
+struct Answer(bool);
+
+impl Prompt for Answer {
+    fn confirm(&self) -> bool {
+        return self.0;
+    }
+
+    fn resolve_conflict(&self, _path: &str) -> Option<ConflictResolution> {
+        return None;
+    }
+}
+
+#[test]
+fn unforced_syncs_run_once_confirmed() {
+    let _guard = setup();
+    let config = config();
+    let unforced = |answer: Option<bool>| {
+        prompt::set(answer.map(|answer| Arc::new(Answer(answer)) as Arc<dyn Prompt>));
+        let runner = Arc::new(RecordingRunner::new());
+        runner::set(Some(runner.clone()));
+        let results = Syncer::new(&config).run_folders(
+            &config.folders["project_local"],
+            &config.folders["project_copy"],
+            &Some("src".to_string()),
+        );
+        runner::set(None);
+        prompt::set(None);
+        return (
+            results[0].as_ref().unwrap().skipped,
+            runner.commands().len(),
+        );
+    };
+    // Without a prompt the library asks nobody and declines
+    assert_eq!(unforced(None), (true, 4));
+    assert_eq!(unforced(Some(false)), (true, 4));
+    assert_eq!(unforced(Some(true)), (false, 14));
+}
+
+#[test]
+fn host_is_not_looked_up_through_the_runner() {
+    let _guard = setup();
+    let runner = Arc::new(RecordingRunner::new());
+    runner::set(Some(runner.clone()));
+    let host = history::host();
+    runner::set(None);
+    assert!(!host.is_empty());
+    assert!(runner.commands().is_empty());
+}
+
+#[test]
+fn caller_options_fill_in_for_the_link() {
+    let config = config();
+    let mut linked = Link {
+        name: "layered".to_string(),
+        local: config.folders["project_local"].clone(),
+        target: config.folders["project"].clone(),
+        paths: Vec::new(),
+        partial_only: false,
+        compression: None,
+        transfer: None,
+        checksum: Some(ChecksumAlgorithm::Sha256),
+        verify: false,
+        low_priority: None,
+        jobs: None,
+        bundle: false,
+        exclude: vec!["*.log".to_string()],
+        schedule: None,
+        hooks: Hooks::new(None, Some("make clean".to_string()), None),
+    };
+    let caller = SyncOptions {
+        transfer: Some(TransferMode::Stream),
+        checksum: Some(ChecksumAlgorithm::Blake3),
+        exclude: vec!["target".to_string()],
+        hooks: Hooks::new(
+            Some("make".to_string()),
+            Some("true".to_string()),
+            Some(false),
+        ),
+        ..SyncOptions::default()
+    };
+    let options = link::options(&linked, &caller);
+    assert_eq!(options.transfer, Some(TransferMode::Stream));
+    assert_eq!(options.checksum, Some(ChecksumAlgorithm::Sha256));
+    assert_eq!(options.exclude, ["*.log", "target"]);
+    assert_eq!(options.hooks.pre_sync.as_deref(), Some("make"));
+    assert!(!options.hooks.pre_sync_abort);
+    assert_eq!(options.hooks.post_sync.as_deref(), Some("make clean"));
+
+    linked.hooks = Hooks::new(Some("make check".to_string()), None, None);
+    let options = link::options(&linked, &caller);
+    assert_eq!(options.hooks.pre_sync.as_deref(), Some("make check"));
+    assert!(options.hooks.pre_sync_abort);
+}
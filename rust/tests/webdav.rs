@@ -0,0 +1,349 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+mod common;
+
+use common::{read, run, scratch, write};
+use directory_sync::model::folder::FolderType;
+use directory_sync::service::{endpoint, runner, webdav};
+use directory_sync::{Config, Direction};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::thread;
+
+static SERVER: OnceLock<String> = OnceLock::new();
+const SUITE: &str = "webdav";
+
+// A scratch tree per test, the server shares <scratch>/server under its /dav url
+fn setup(test: &str) -> PathBuf {
+    return common::setup(SUITE, test, &["project", "restore"], |scratch| {
+        fs::create_dir_all(scratch.join("server/dav")).expect("Scratch server");
+    });
+}
+
+// Just enough of WebDAV for curl: PROPFIND with depth 1, MKCOL, PUT, GET, DELETE and MOVE.
+// Files named "forbidden" are refused.
+fn server() -> &'static str {
+    return SERVER.get_or_init(|| {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Bind server");
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let root = scratch(SUITE).join("server");
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let root = root.clone();
+                thread::spawn(move || serve(stream, &root));
+            }
+        });
+        address
+    });
+}
+
+fn serve(stream: TcpStream, root: &Path) -> () {
+    let mut reader = BufReader::new(stream.try_clone().expect("Clone stream"));
+    let mut stream = stream;
+    loop {
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+            return;
+        }
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or_default().to_string();
+        let path = decode(parts.next().unwrap_or_default());
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).expect("Read header");
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.insert(name.to_lowercase(), value.trim().to_string());
+            }
+        }
+        if headers
+            .get("expect")
+            .is_some_and(|expect| expect == "100-continue")
+        {
+            stream
+                .write_all(b"HTTP/1.1 100 Continue\r\n\r\n")
+                .expect("Continue");
+        }
+        let length = headers
+            .get("content-length")
+            .and_then(|length| length.parse::<usize>().ok())
+            .unwrap_or(0);
+        let mut body = vec![0u8; length];
+        reader.read_exact(&mut body).expect("Read body");
+
+        let target = root.join(path.trim_matches('/'));
+        let parent_exists = target.parent().is_some_and(|parent| parent.is_dir());
+        let (status, response) = match method.as_str() {
+            "PUT" | "GET" if path.contains("forbidden") => (403, Vec::new()),
+            "PROPFIND" if target.exists() => (207, propfind(&path, &target)),
+            "MKCOL" if target.exists() => (405, Vec::new()),
+            "MKCOL" if parent_exists => {
+                fs::create_dir(&target).expect("Create collection");
+                (201, Vec::new())
+            }
+            "PUT" if parent_exists => {
+                fs::write(&target, &body).expect("Write file");
+                (201, Vec::new())
+            }
+            "GET" if target.is_file() => (200, fs::read(&target).expect("Read file")),
+            "DELETE" if target.is_dir() => {
+                fs::remove_dir_all(&target).expect("Delete collection");
+                (204, Vec::new())
+            }
+            "DELETE" if target.is_file() => {
+                fs::remove_file(&target).expect("Delete file");
+                (204, Vec::new())
+            }
+            "MOVE" if target.exists() => {
+                let destination = headers.get("destination").expect("Destination");
+                let destination = decode(&destination[destination.find("/dav").unwrap()..]);
+                let destination = root.join(destination.trim_matches('/'));
+                let _ = fs::remove_dir_all(&destination);
+                fs::rename(&target, &destination).expect("Move");
+                (201, Vec::new())
+            }
+            "MKCOL" | "PUT" => (409, Vec::new()),
+            _ => (404, Vec::new()),
+        };
+        let head = format!(
+            "HTTP/1.1 {} Status\r\nContent-Length: {}\r\n\r\n",
+            status,
+            response.len()
+        );
+        stream.write_all(head.as_bytes()).expect("Write head");
+        stream.write_all(&response).expect("Write body");
+    }
+}
+
+fn propfind(path: &str, target: &Path) -> Vec<u8> {
+    let mut entries = vec![(path.trim_end_matches('/').to_string(), target.to_path_buf())];
+    if target.is_dir() {
+        for child in fs::read_dir(target).expect("Read collection").flatten() {
+            entries.push((
+                format!(
+                    "{}/{}",
+                    path.trim_end_matches('/'),
+                    child.file_name().to_string_lossy()
+                ),
+                child.path(),
+            ));
+        }
+    }
+    let mut xml = String::from("<?xml version=\"1.0\"?><d:multistatus xmlns:d=\"DAV:\">");
+    for (href, entry) in entries {
+        let prop = match entry.is_dir() {
+            true => "<d:resourcetype><d:collection/></d:resourcetype>".to_string(),
+            false => format!(
+                "<d:resourcetype/><d:getcontentlength>{}</d:getcontentlength>",
+                fs::metadata(&entry).unwrap().len()
+            ),
+        };
+        xml.push_str(&format!(
+            "<d:response><d:href>{}{}</d:href><d:propstat><d:prop>{}</d:prop></d:propstat></d:response>",
+            encode(&href),
+            if entry.is_dir() { "/" } else { "" },
+            prop
+        ));
+    }
+    xml.push_str("</d:multistatus>");
+    return xml.into_bytes();
+}
+
+fn encode(path: &str) -> String {
+    return path
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect();
+}
+
+fn decode(path: &str) -> String {
+    let mut decoded = Vec::new();
+    let mut bytes = path.bytes();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'%' => {
+                let hex: String = bytes.by_ref().take(2).map(char::from).collect();
+                decoded.push(u8::from_str_radix(&hex, 16).expect("Percent escape"));
+            }
+            _ => decoded.push(byte),
+        }
+    }
+    return String::from_utf8(decoded).expect("Utf-8 path");
+}
+
+fn config(root: &Path, prefix: &str) -> Config {
+    return common::config(format!(
+        r#"
+[folders.project]
+path = "{root}/project"
+target = "local"
+
+[folders.restore]
+path = "{root}/restore"
+target = "local"
+
+[folders.share]
+path = "/{prefix}"
+target = "webdav"
+url = "{url}/dav"
+
+[links.share]
+local = "project"
+target = "share"
+paths = ["src"]
+partial_only = "false"
+exclude = ["*.log"]
+
+[links.restore]
+local = "restore"
+target = "share"
+paths = ["src"]
+partial_only = "false"
+exclude = ["*.log"]
+
+[ssh]
+"#,
+        root = root.display(),
+        url = server(),
+    ));
+}
+
+#[test]
+fn push_and_pull() {
+    let root = setup("push_and_pull");
+    let config = config(&root, "push_and_pull/projects");
+    write(root.join("project/src/main.rs"), "fn main() {}\n");
+    write(root.join("project/src/docs/it's spaced.txt"), "quoted\n");
+    write(root.join("project/src/debug.log"), "left out\n");
+    fs::create_dir_all(root.join("project/src/empty")).unwrap();
+    let shared = scratch(SUITE).join("server/dav/push_and_pull/projects");
+    write(shared.join("src/stale.txt"), "replaced\n");
+
+    let reports = run(&config, "share", Direction::Push);
+    assert!(reports[0].is_ok(), "{:?}", reports[0]);
+    assert_eq!(read(shared.join("src/main.rs")), "fn main() {}\n");
+    assert_eq!(read(shared.join("src/docs/it's spaced.txt")), "quoted\n");
+    assert!(shared.join("src/empty").is_dir());
+    assert!(!shared.join("src/debug.log").exists());
+    assert!(!shared.join("src/stale.txt").exists());
+    // Only the synced folder is left next to the destination
+    assert_eq!(fs::read_dir(&shared).unwrap().count(), 1);
+
+    write(root.join("restore/src/stale.txt"), "replaced\n");
+    write(shared.join("src/server.log"), "left out\n");
+    let reports = run(&config, "restore", Direction::Pull);
+    let report = reports[0].as_ref().unwrap();
+    assert_eq!((report.files, report.bytes), (2, 20));
+    assert!(!root.join("restore/src/server.log").exists());
+    assert_eq!(read(root.join("restore/src/main.rs")), "fn main() {}\n");
+    assert_eq!(
+        read(root.join("restore/src/docs/it's spaced.txt")),
+        "quoted\n"
+    );
+    assert!(root.join("restore/src/empty").is_dir());
+    assert!(!root.join("restore/src/stale.txt").exists());
+    assert_eq!(fs::read_dir(root.join("restore")).unwrap().count(), 1);
+}
+
+#[test]
+fn missing_paths_and_listing() {
+    let root = setup("missing_paths_and_listing");
+    let config = config(&root, "missing_paths_and_listing");
+    assert_eq!(
+        run(&config, "restore", Direction::Pull)[0]
+            .as_ref()
+            .unwrap_err(),
+        "Error: From Folder Does Not Exist"
+    );
+
+    let shared = scratch(SUITE).join("server/dav/missing_paths_and_listing");
+    write(shared.join("src/a.txt"), "abc");
+    fs::create_dir_all(shared.join("src/sub")).unwrap();
+    let FolderType::WebDav(server) = &config.folders["share"].target else {
+        panic!("Not a webdav folder");
+    };
+    let tree = webdav::tree(server, "/missing_paths_and_listing/src")
+        .unwrap()
+        .unwrap();
+    let paths: Vec<(&str, bool, u64)> = tree
+        .iter()
+        .map(|entry| (entry.path.as_str(), entry.is_dir, entry.size))
+        .collect();
+    assert_eq!(paths, [("a.txt", false, 3), ("sub", true, 0)]);
+    let share = endpoint::get(&config.folders["share"], &config.ssh_servers).unwrap();
+    assert!(share.remove(&["src".to_string()]).is_err());
+    assert_eq!(
+        run(&config, "restore", Direction::Sync)[0]
+            .as_ref()
+            .unwrap_err(),
+        "Two way syncs are not supported for webdav folders"
+    );
+}
+
+#[test]
+fn propfind_entities_are_decoded() {
+    let entries = webdav::parse_propfind(
+        "<d:multistatus xmlns:d=\"DAV:\"><d:response>\
+         <d:href>/dav/a&amp;b&lt;c&gt;&quot;d&apos;&#233;&#x4E2D;&unknown;.txt</d:href>\
+         <d:propstat><d:prop><d:getcontentlength>4</d:getcontentlength></d:prop></d:propstat>\
+         </d:response></d:multistatus>",
+    );
+    assert_eq!(entries.len(), 1);
+    assert_eq!(
+        entries[0].path,
+        "/dav/a&b<c>\"d'\u{e9}\u{4e2d}&unknown;.txt"
+    );
+    assert_eq!(entries[0].size, 4);
+}
+
+#[test]
+fn failed_transfer_fails_the_sync() {
+    let root = setup("failed_transfer_fails_the_sync");
+    let config = config(&root, "failed_transfer_fails_the_sync");
+    // Later files arriving must not hide the refused one
+    for name in ["a.txt", "forbidden.txt", "z.txt"] {
+        write(root.join("project/src").join(name), name);
+    }
+    let shared = scratch(SUITE).join("server/dav/failed_transfer_fails_the_sync");
+    write(shared.join("src/kept.txt"), "kept\n");
+
+    let reports = run(&config, "share", Direction::Push);
+    assert_eq!(reports[0].as_ref().unwrap_err(), "Failed to Upload Files");
+    assert_eq!(read(shared.join("src/kept.txt")), "kept\n");
+    assert_eq!(fs::read_dir(&shared).unwrap().count(), 1);
+
+    for name in ["a.txt", "forbidden.txt", "z.txt"] {
+        write(shared.join("src").join(name), name);
+    }
+    write(root.join("restore/src/kept.txt"), "kept\n");
+    let reports = run(&config, "restore", Direction::Pull);
+    assert_eq!(reports[0].as_ref().unwrap_err(), "Failed to Download Files");
+    assert_eq!(read(root.join("restore/src/kept.txt")), "kept\n");
+    assert_eq!(fs::read_dir(root.join("restore")).unwrap().count(), 1);
+}
+
+#[test]
+fn batches_keep_curl_arguments_short() {
+    let paths: Vec<String> = (0..1000)
+        .map(|index| format!("/{}/{}", "x".repeat(200), index))
+        .collect();
+    let batches = runner::batches(&paths, |path| path.len());
+    assert!(batches.len() > 1);
+    assert_eq!(batches.concat(), paths);
+    for batch in batches {
+        assert!(batch.iter().map(|path| path.len()).sum::<usize>() <= 64 * 1024);
+    }
+}